@@ -0,0 +1,53 @@
+use std::{env, fs, path::Path};
+
+// Lists every #[aoc(dayN, partP[, name])] solver as a solution! entry for
+// src/runner.rs, so the runner's registry follows the attributes.
+fn main() {
+    let src = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("src");
+    println!("cargo:rerun-if-changed={}", src.display());
+
+    let mut solutions = vec![];
+    for entry in fs::read_dir(&src).unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        if !(name.starts_with("day") && name.ends_with(".rs")) {
+            continue;
+        }
+        let text = fs::read_to_string(&path).unwrap();
+        for line in text.lines() {
+            let Some(args) = line
+                .trim()
+                .strip_prefix("#[aoc(")
+                .and_then(|l| l.strip_suffix(")]"))
+            else {
+                continue;
+            };
+            let args = args.split(',').map(str::trim).collect::<Vec<_>>();
+            let number = |arg: Option<&&str>, prefix: &str| -> u32 {
+                arg.and_then(|a| a.strip_prefix(prefix))
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or_else(|| panic!("{}: cannot read {:?}", name, line))
+            };
+            let (day, part) = (number(args.first(), "day"), number(args.get(1), "part"));
+            solutions.push((day, part, args.get(2).map(|n| n.to_string())));
+        }
+    }
+    solutions.sort();
+
+    let mut code = "&[\n".to_string();
+    for (day, part, name) in solutions {
+        code += &match name {
+            Some(name) => format!(
+                "    solution!({}, {}, {:?}, day{}_part{}_{}),\n",
+                day, part, name, day, part, name
+            ),
+            None => format!(
+                "    solution!({}, {}, day{}_part{}),\n",
+                day, part, day, part
+            ),
+        };
+    }
+    code += "]\n";
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("solutions.rs");
+    fs::write(out, code).unwrap();
+}
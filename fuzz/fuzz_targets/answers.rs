@@ -0,0 +1,10 @@
+#![no_main]
+
+use advent_2018::answers::Answers;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        let _ = text.parse::<Answers>();
+    }
+});
@@ -0,0 +1,21 @@
+use advent_2018::runner;
+
+// Runs every generator registered for the day. A parser may reject the input
+// but must not panic; cargo-fuzz builds abort on the panic itself, other
+// builds see it as the error `runner` turns it into.
+pub fn generate(day: u32, data: &[u8]) {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    for solution in runner::find(day, None) {
+        if let Err(e) = solution.generate(text) {
+            let message = e.to_string();
+            assert!(
+                !message.starts_with("generating panicked"),
+                "{}: {}",
+                solution.label(),
+                message
+            );
+        }
+    }
+}
@@ -0,0 +1,349 @@
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    path::Path,
+};
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::runner::{Outcome, Solution};
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Answers(BTreeMap<(u32, u32), String>);
+
+fn number(s: &str, prefix: &str) -> Option<u32> {
+    s.strip_prefix(prefix)?.parse().ok()
+}
+
+// Quoted answers take the TOML basic string escapes \n, \t, \" and \\, so
+// multi-line answers fit on one line.
+fn value(s: &str) -> anyhow::Result<String> {
+    if let Some(quoted) = s.strip_prefix('"') {
+        let mut text = String::new();
+        let mut chars = quoted.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if chars.as_str().is_empty() => return Ok(text),
+                '"' => bail!("unexpected {:?} after the closing quote", chars.as_str()),
+                '\\' => text.push(match chars.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some(c) => bail!("unknown escape \\{}", c),
+                    None => break,
+                }),
+                c => text.push(c),
+            }
+        }
+        bail!("unterminated string")
+    } else if s.parse::<i64>().is_ok() {
+        Ok(s.to_string())
+    } else {
+        bail!("expected a quoted string or an integer")
+    }
+}
+
+fn escape(answer: &str) -> String {
+    answer
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+impl std::str::FromStr for Answers {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut answers = BTreeMap::new();
+        let mut day = None;
+        for (i, raw) in s.lines().enumerate() {
+            let line = strip_comment(raw).trim();
+            let at = || format!("line {}: {:?}", i + 1, raw);
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                day = Some(
+                    number(section.trim(), "day")
+                        .context("expected [dayN]")
+                        .with_context(at)?,
+                );
+                continue;
+            }
+            let (key, answer) = line
+                .split_once('=')
+                .context("expected key = value")
+                .with_context(at)?;
+            let day = day
+                .context("answer outside a [dayN] section")
+                .with_context(at)?;
+            let part = number(key.trim(), "part")
+                .context("expected partN")
+                .with_context(at)?;
+            let answer = value(answer.trim()).with_context(at)?;
+            if answers.insert((day, part), answer).is_some() {
+                let duplicate = anyhow!("duplicate answer for day{} part{}", day, part);
+                return Err(duplicate.context(at()));
+            }
+        }
+        Ok(Answers(answers))
+    }
+}
+
+impl std::fmt::Display for Answers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut section = None;
+        for (&(day, part), answer) in &self.0 {
+            if section != Some(day) {
+                if section.is_some() {
+                    writeln!(f)?;
+                }
+                writeln!(f, "[day{}]", day)?;
+                section = Some(day);
+            }
+            writeln!(f, "part{} = \"{}\"", part, escape(answer))?;
+        }
+        Ok(())
+    }
+}
+
+impl Answers {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        std::fs::read_to_string(path)
+            .with_context(|| format!("reading {}", path.display()))?
+            .parse()
+            .with_context(|| format!("parsing {}", path.display()))
+    }
+
+    // Keeps the comment block at the top of any existing file.
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let existing = std::fs::read_to_string(path).unwrap_or_default();
+        let mut text = existing
+            .lines()
+            .take_while(|line| line.starts_with('#'))
+            .map(|line| format!("{}\n", line))
+            .collect::<String>();
+        if !text.is_empty() && !self.0.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(&self.to_string());
+        std::fs::write(path, text).with_context(|| format!("writing {}", path.display()))
+    }
+
+    // Keeps any answer already recorded; true if this one was new.
+    pub fn record(&mut self, day: u32, part: u32, answer: &str) -> bool {
+        match self.0.entry((day, part)) {
+            Entry::Vacant(entry) => {
+                entry.insert(answer.to_string());
+                true
+            }
+            Entry::Occupied(_) => false,
+        }
+    }
+
+    pub fn get(&self, day: u32, part: u32) -> Option<&str> {
+        self.0.get(&(day, part)).map(String::as_str)
+    }
+
+    pub fn verdict(&self, solution: &Solution, run: anyhow::Result<Outcome>) -> Verdict {
+        match (run, self.get(solution.day, solution.part)) {
+            (Err(e), _) => Verdict::Error(e),
+            (Ok(outcome), None) => Verdict::Missing(outcome),
+            (Ok(outcome), Some(expected)) if outcome.answer == expected => Verdict::Pass(outcome),
+            (Ok(outcome), Some(expected)) => Verdict::Fail {
+                expected: expected.to_string(),
+                outcome,
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Verdict {
+    Pass(Outcome),
+    Fail { expected: String, outcome: Outcome },
+    Missing(Outcome),
+    Error(anyhow::Error),
+}
+
+impl std::fmt::Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Verdict::Pass(o) => write!(f, "pass  [{:?}]", o.generate + o.run),
+            Verdict::Fail { expected, outcome } => {
+                write!(f, "FAIL  expected {}, got {}", expected, outcome.answer)
+            }
+            Verdict::Missing(o) => write!(f, "missing answer, got {}", o.answer),
+            Verdict::Error(e) => write!(f, "FAIL  {:#}", e),
+        }
+    }
+}
+
+impl Verdict {
+    pub fn passed(&self) -> bool {
+        matches!(self, Verdict::Pass(_))
+    }
+
+    pub fn failed(&self) -> bool {
+        matches!(self, Verdict::Fail { .. } | Verdict::Error(_))
+    }
+}
+
+#[cfg(test)]
+mod known {
+    use super::*;
+    use crate::{
+        runner::find,
+        synth::{mangle, Rng},
+    };
+
+    const ANSWERS: &str = r#"
+# answers for our inputs
+[day1]
+part1 = 2   # trailing comment
+part2 = "14" # quoted
+
+[day5]
+part1 = "10"
+"#;
+
+    #[test]
+    fn parses() {
+        let answers: Answers = ANSWERS.parse().unwrap();
+        assert_eq!(answers.get(1, 1), Some("2"));
+        assert_eq!(answers.get(1, 2), Some("14"));
+        assert_eq!(answers.get(5, 1), Some("10"));
+        assert_eq!(answers.get(5, 2), None);
+    }
+
+    #[test_case("part1 = 1" => "line 1: \"part1 = 1\": answer outside a [dayN] section")]
+    #[test_case("[first]" => "line 1: \"[first]\": expected [dayN]")]
+    #[test_case("[day1]\npart1 = 1\npart1 = 2" => "line 3: \"part1 = 2\": duplicate answer for day1 part1")]
+    #[test_case("[day1]\npart1 = \"1" => "line 2: \"part1 = \\\"1\": unterminated string")]
+    #[test_case("[day1]\npart1 = one" => "line 2: \"part1 = one\": expected a quoted string or an integer")]
+    #[test_case("[day1]\nfirst = 1" => "line 2: \"first = 1\": expected partN")]
+    #[test_case("[day1]\npart1 = \"a\\qb\"" => "line 2: \"part1 = \\\"a\\\\qb\\\"\": unknown escape \\q")]
+    #[test_case("[day1]\npart1 = \"a\" b" => "line 2: \"part1 = \\\"a\\\" b\": unexpected \" b\" after the closing quote")]
+    #[test_case("[day1]\npart1 = \"a\\\"" => "line 2: \"part1 = \\\"a\\\\\\\"\": unterminated string")]
+    fn rejects(input: &str) -> String {
+        format!("{:#}", input.parse::<Answers>().unwrap_err())
+    }
+
+    #[test]
+    fn round_trips() {
+        let answers: Answers = ANSWERS.parse().unwrap();
+        assert_eq!(
+            answers.to_string(),
+            "[day1]\npart1 = \"2\"\npart2 = \"14\"\n\n[day5]\npart1 = \"10\"\n"
+        );
+        crate::parse::round_trips([answers]);
+    }
+
+    #[test]
+    fn escapes() {
+        let answers: Answers = r##"[day10]
+part1 = "At second 3\n#..#\n\t\"#\"\\" # comment
+"##
+        .parse()
+        .unwrap();
+        assert_eq!(answers.get(10, 1), Some("At second 3\n#..#\n\t\"#\"\\"));
+        crate::parse::round_trips([answers]);
+    }
+
+    #[test]
+    fn records_multi_line_answers() {
+        let input = crate::synth::input(10, 1, 0).unwrap();
+        let solution = find(10, Some(1)).next().unwrap();
+        let answer = solution.run(&input).unwrap().answer;
+        assert!(answer.contains('\n'));
+
+        let mut answers = Answers::default();
+        assert!(answers.record(10, 1, &answer));
+        assert!(!answers.record(10, 1, "something else"));
+        let answers: Answers = answers.to_string().parse().unwrap();
+        assert!(answers.verdict(solution, solution.run(&input)).passed());
+    }
+
+    #[test]
+    fn saves() {
+        let dir = std::env::temp_dir().join(format!("answers-{}-saves", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("answers.toml");
+        std::fs::write(&path, "# header\n# more\n\n[day1]\npart1 = 2\n").unwrap();
+        let mut answers = Answers::load(&path).unwrap();
+        answers.record(1, 2, "two\nlines");
+        answers.save(&path).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "# header\n# more\n\n[day1]\npart1 = \"2\"\npart2 = \"two\\nlines\"\n"
+        );
+        assert_eq!(Answers::load(&path).unwrap(), answers);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    // Smoke test only, fuzz/ has a cargo-fuzz target for the answers file.
+    #[test]
+    fn survives_mangled_files() {
+        let rng = &mut Rng::new(2018);
+        for _ in 0..500 {
+            let _ = mangle(rng, ANSWERS).parse::<Answers>();
+        }
+    }
+
+    #[test]
+    fn verdicts() {
+        let answers: Answers = ANSWERS.parse().unwrap();
+        let check = |day, part, input: &str| {
+            let solution = find(day, Some(part)).next().unwrap();
+            answers.verdict(solution, solution.run(input))
+        };
+        assert!(check(1, 1, "+1\n-2\n+3").passed());
+        assert!(check(1, 2, "+1\n-2\n+3").failed());
+        assert!(check(1, 2, "+1\nseven").failed());
+        assert!(matches!(
+            check(5, 2, "dabAcCaCBAcCcaDA"),
+            Verdict::Missing(_)
+        ));
+        assert_eq!(
+            check(1, 2, "+1\n-2\n+3").to_string(),
+            "FAIL  expected 14, got 1"
+        );
+    }
+
+    #[test]
+    #[ignore = "needs input/2018 and answers/2018.toml; run with --release -- --ignored"]
+    fn recorded() {
+        let answers = Answers::load("answers/2018.toml").unwrap();
+        let failures = crate::runner::SOLUTIONS
+            .iter()
+            .filter_map(|solution| {
+                let input =
+                    std::fs::read_to_string(crate::runner::input_path("input/2018", solution.day))
+                        .ok()?;
+                let verdict = answers.verdict(solution, solution.run(&input));
+                verdict
+                    .failed()
+                    .then(|| format!("{}: {}", solution.label(), verdict))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(failures, Vec::<String>::new());
+    }
+}
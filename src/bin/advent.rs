@@ -0,0 +1,321 @@
+use std::{
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use advent_2018::{
+    answers::Answers,
+    runner::{self, Comparison, Solution},
+    synth,
+};
+use anyhow::{bail, Context};
+
+const USAGE: &str = "\
+usage: advent [--compare] <day> [part] [input|-]
+       advent [--compare] --all [input-dir]
+       advent --check [input-dir] [answers]
+       advent --record [input-dir] [answers]
+       advent --synth <day> [scale] [seed]
+
+Runs every registered implementation of the given day (and part) on the
+input file, or stdin when it is missing or '-'. With --all, each day reads
+day<N>.txt from input-dir (default input/2018) and days without an input
+are skipped. With --compare, the implementations of each part are listed
+side by side and any disagreement between them is a failure. With --check,
+every implementation is run on the inputs in input-dir and compared with
+the answers recorded in answers (default answers/2018.toml). With --record,
+the answer every implementation of a part agrees on is added to answers
+when it has none yet; existing answers are never overwritten. With --synth,
+a random but valid input for the day is printed instead (default scale 1,
+seed 0), ready to be piped back into advent.";
+
+enum Command {
+    Day {
+        day: u32,
+        part: Option<u32>,
+        input: Option<PathBuf>,
+    },
+    All {
+        dir: PathBuf,
+    },
+    Check {
+        dir: PathBuf,
+        answers: PathBuf,
+    },
+    Record {
+        dir: PathBuf,
+        answers: PathBuf,
+    },
+    Synth {
+        day: u32,
+        scale: usize,
+        seed: u64,
+    },
+}
+
+fn number(arg: &str, prefix: &str) -> Option<u32> {
+    arg.strip_prefix(prefix).unwrap_or(arg).parse().ok()
+}
+
+#[derive(Clone, Copy)]
+enum Mode {
+    Report,
+    Compare,
+}
+
+fn parse_args(args: &[String]) -> anyhow::Result<(Command, Mode)> {
+    match args {
+        [flag, rest @ ..] if flag == "--compare" => match parse_command(rest)? {
+            Command::Check { .. } => bail!("--compare cannot be combined with --check"),
+            Command::Record { .. } => bail!("--compare cannot be combined with --record"),
+            Command::Synth { .. } => bail!("--compare cannot be combined with --synth"),
+            command => Ok((command, Mode::Compare)),
+        },
+        _ => Ok((parse_command(args)?, Mode::Report)),
+    }
+}
+
+fn parse_command(args: &[String]) -> anyhow::Result<Command> {
+    match args {
+        [flag, rest @ ..] if flag == "--all" => match rest {
+            [] => Ok(Command::All {
+                dir: PathBuf::from("input/2018"),
+            }),
+            [dir] => Ok(Command::All {
+                dir: PathBuf::from(dir),
+            }),
+            _ => bail!("too many arguments"),
+        },
+        [flag, rest @ ..] if flag == "--check" || flag == "--record" => {
+            let mut rest = rest.iter().map(PathBuf::from);
+            let dir = rest.next().unwrap_or_else(|| PathBuf::from("input/2018"));
+            let answers = rest
+                .next()
+                .unwrap_or_else(|| PathBuf::from("answers/2018.toml"));
+            if rest.next().is_some() {
+                bail!("too many arguments");
+            }
+            Ok(match flag.as_str() {
+                "--check" => Command::Check { dir, answers },
+                _ => Command::Record { dir, answers },
+            })
+        }
+        [flag, day, rest @ ..] if flag == "--synth" => {
+            let day = number(day, "day").with_context(|| format!("bad day {:?}", day))?;
+            let (scale, seed) = match rest {
+                [] => (1, 0),
+                [scale] => (scale.parse()?, 0),
+                [scale, seed] => (scale.parse()?, seed.parse()?),
+                _ => bail!("too many arguments"),
+            };
+            Ok(Command::Synth { day, scale, seed })
+        }
+        [day, rest @ ..] => {
+            let day = number(day, "day").with_context(|| format!("bad day {:?}", day))?;
+            let (part, rest) = match rest.split_first() {
+                Some((part, rest)) if number(part, "part").is_some() => {
+                    (number(part, "part"), rest)
+                }
+                _ => (None, rest),
+            };
+            let input = match rest {
+                [] => None,
+                [path] if path == "-" => None,
+                [path] => Some(PathBuf::from(path)),
+                _ => bail!("too many arguments"),
+            };
+            Ok(Command::Day { day, part, input })
+        }
+        [] => bail!("missing day"),
+    }
+}
+
+fn report(solution: &Solution, input: &str) -> bool {
+    match solution.run(input) {
+        Ok(outcome) => {
+            println!(
+                "{}: {}  [generator {:?}, runner {:?}]",
+                solution.label(),
+                outcome.answer,
+                outcome.generate,
+                outcome.run
+            );
+            true
+        }
+        Err(e) => {
+            eprintln!("{}: {:#}", solution.label(), e);
+            false
+        }
+    }
+}
+
+fn compare(comparison: &Comparison) -> bool {
+    let label = format!("day{} part{}", comparison.day, comparison.part);
+    let agreed = comparison.agreed();
+    match (agreed, comparison.runs.len()) {
+        (Some(answer), 1) => println!("{}: {}  [single implementation]", label, answer),
+        (Some(answer), n) => println!("{}: {}  [{} implementations agree]", label, answer, n),
+        _ if comparison.runs.iter().any(|(_, run)| run.is_err()) => {
+            println!("{}: failed", label)
+        }
+        _ => println!("{}: implementations disagree", label),
+    }
+    let width = comparison
+        .runs
+        .iter()
+        .map(|(s, _)| s.name.unwrap_or("default").len())
+        .max()
+        .unwrap_or(0);
+    for (solution, run) in &comparison.runs {
+        let name = solution.name.unwrap_or("default");
+        match run {
+            Ok(outcome) => println!(
+                "  {:width$}  {:>12?}  {:>12?}  {}",
+                name,
+                outcome.generate,
+                outcome.run,
+                outcome.answer,
+                width = width
+            ),
+            Err(e) => println!("  {:width$}  {:#}", name, e, width = width),
+        }
+    }
+    agreed.is_some()
+}
+
+fn check(day: u32, part: Option<u32>, input: &str, mode: Mode) -> bool {
+    let mut ok = true;
+    match mode {
+        Mode::Report => {
+            for solution in runner::find(day, part) {
+                ok &= report(solution, input);
+            }
+        }
+        Mode::Compare => {
+            for comparison in runner::compare(day, part, input) {
+                ok &= compare(&comparison);
+            }
+        }
+    }
+    ok
+}
+
+fn check_answers(dir: &Path, answers: &Answers) -> bool {
+    let (mut passed, mut failed, mut missing) = (0, 0, 0);
+    for day in runner::days() {
+        let path = runner::input_path(dir, day);
+        let Ok(input) = std::fs::read_to_string(&path) else {
+            println!("day{}: missing, no {}", day, path.display());
+            missing += runner::find(day, None).count();
+            continue;
+        };
+        for solution in runner::find(day, None) {
+            let verdict = answers.verdict(solution, solution.run(&input));
+            println!("{}: {}", solution.label(), verdict);
+            if verdict.passed() {
+                passed += 1;
+            } else if verdict.failed() {
+                failed += 1;
+            } else {
+                missing += 1;
+            }
+        }
+    }
+    println!("{} passed, {} failed, {} missing", passed, failed, missing);
+    failed == 0
+}
+
+fn record_answers(dir: &Path, answers: &mut Answers) -> bool {
+    let (mut recorded, mut kept, mut failed) = (0, 0, 0);
+    for day in runner::days() {
+        let path = runner::input_path(dir, day);
+        let Ok(input) = std::fs::read_to_string(&path) else {
+            println!("day{}: skipped, no {}", day, path.display());
+            continue;
+        };
+        for comparison in runner::compare(day, None, &input) {
+            let label = format!("day{} part{}", comparison.day, comparison.part);
+            match comparison.agreed() {
+                Some(answer) if answers.record(comparison.day, comparison.part, answer) => {
+                    println!("{}: recorded", label);
+                    recorded += 1;
+                }
+                Some(_) => kept += 1,
+                None => {
+                    println!("{}: not recorded, implementations fail or disagree", label);
+                    failed += 1;
+                }
+            }
+        }
+    }
+    println!("{} recorded, {} kept, {} failed", recorded, kept, failed);
+    failed == 0
+}
+
+fn run((command, mode): (Command, Mode)) -> anyhow::Result<bool> {
+    let mut ok = true;
+    match command {
+        Command::Day { day, part, input } => {
+            if runner::find(day, part).next().is_none() {
+                bail!("no solution registered for day {}", day);
+            }
+            let input = match input {
+                Some(path) => std::fs::read_to_string(&path)
+                    .with_context(|| format!("reading {}", path.display()))?,
+                None => {
+                    let mut input = String::new();
+                    io::stdin().read_to_string(&mut input)?;
+                    input
+                }
+            };
+            ok &= check(day, part, &input, mode);
+        }
+        Command::All { dir } => {
+            for day in runner::days() {
+                let path = runner::input_path(&dir, day);
+                let Ok(input) = std::fs::read_to_string(&path) else {
+                    println!("day{}: skipped, no {}", day, path.display());
+                    continue;
+                };
+                ok &= check(day, None, &input, mode);
+            }
+        }
+        Command::Check { dir, answers } => {
+            ok &= check_answers(&dir, &Answers::load(answers)?);
+        }
+        Command::Record { dir, answers: path } => {
+            let mut answers = match path.exists() {
+                true => Answers::load(&path)?,
+                false => Answers::default(),
+            };
+            ok &= record_answers(&dir, &mut answers);
+            answers.save(&path)?;
+        }
+        Command::Synth { day, scale, seed } => {
+            let input = synth::input(day, scale, seed)
+                .with_context(|| format!("no generator for day {}", day))?;
+            print!("{}", input);
+            if !input.ends_with('\n') {
+                println!();
+            }
+        }
+    }
+    Ok(ok)
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    match parse_args(&args).and_then(run) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {:#}\n\n{}", e, USAGE);
+            ExitCode::from(2)
+        }
+    }
+}
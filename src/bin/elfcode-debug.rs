@@ -0,0 +1,162 @@
+use std::io::{self, BufRead, Write};
+
+use advent_2018::wasm::{
+    debug::{Debugger, Stop, Watch},
+    disasm::disassemble,
+    Cpu, Dialect, Number,
+};
+use anyhow::{bail, Context};
+
+const HELP: &str = "\
+s [n]         step n instructions (default 1)
+c [n]         continue for up to n cycles (default 10000000) until a
+              breakpoint, watchpoint, fault or halt
+b <pc>        set a breakpoint
+d <pc>        delete a breakpoint
+w r<n>        watch for register n changing
+w r<n> <v>    watch for register n becoming v
+u r<n> [v]    remove a watchpoint
+back [n]      step backwards n instructions (default 1)
+set r<n> <v>  set register n to v
+r             dump registers
+l             list the disassembly
+i             show breakpoints and watchpoints
+q             quit";
+
+fn register(arg: Option<&str>) -> anyhow::Result<usize> {
+    let arg = arg.context("expected a register")?;
+    arg.strip_prefix('r')
+        .unwrap_or(arg)
+        .parse()
+        .with_context(|| format!("bad register {:?}", arg))
+}
+
+fn value(arg: Option<&str>) -> anyhow::Result<Number> {
+    let arg = arg.context("expected a value")?;
+    arg.parse().with_context(|| format!("bad value {:?}", arg))
+}
+
+fn report(debugger: &Debugger, stop: Stop) {
+    match stop {
+        Stop::Step => {}
+        Stop::Breakpoint(pc) => println!("breakpoint at {}", pc),
+        Stop::Watchpoint(watch) => println!("watchpoint {:?}", watch),
+        Stop::Halted => println!("halted"),
+        Stop::Fault(fault) => println!("fault: {}", fault),
+        Stop::Budget => println!("still running"),
+    }
+    println!("{}", debugger.dump());
+    if !debugger.cpu.halted() {
+        println!(
+            "{}",
+            disassemble(&debugger.cpu).line(debugger.pc() as usize)
+        );
+    }
+}
+
+fn command(debugger: &mut Debugger, line: &str) -> anyhow::Result<bool> {
+    let mut words = line.split_ascii_whitespace();
+    match words.next() {
+        None => {}
+        Some("s") => {
+            let count = words.next().map_or(Ok(1), |n| value(Some(n)))?;
+            let mut stop = Stop::Step;
+            for _ in 0..count {
+                stop = debugger.step();
+                if stop != Stop::Step {
+                    break;
+                }
+            }
+            report(debugger, stop);
+        }
+        Some("c") => {
+            let budget = words.next().map_or(Ok(10_000_000), |n| value(Some(n)))?;
+            let stop = debugger.resume(budget);
+            report(debugger, stop);
+        }
+        Some("b") => debugger.break_at(value(words.next())?),
+        Some("d") => {
+            let pc = value(words.next())?;
+            if !debugger.clear_break(pc) {
+                println!("no breakpoint at {}", pc);
+            }
+        }
+        Some(cmd @ ("w" | "u")) => {
+            let reg = register(words.next())?;
+            if reg >= debugger.cpu.registers.len() {
+                bail!("no register r{}", reg);
+            }
+            let watch = match words.next() {
+                Some(v) => Watch::Equals(reg, value(Some(v))?),
+                None => Watch::Changes(reg),
+            };
+            if cmd == "w" {
+                debugger.watch(watch);
+            } else if !debugger.unwatch(watch) {
+                println!("no watchpoint {:?}", watch);
+            }
+        }
+        Some("back") => {
+            let count = words.next().map_or(Ok(1), |n| value(Some(n)))?;
+            for _ in 0..count {
+                if !debugger.back() {
+                    println!("no more history");
+                    break;
+                }
+            }
+            report(debugger, Stop::Step);
+        }
+        Some("set") => {
+            let reg = register(words.next())?;
+            let v = value(words.next())?;
+            match debugger.cpu.registers.get_mut(reg) {
+                Some(r) => *r = v,
+                None => bail!("no register r{}", reg),
+            }
+        }
+        Some("r") => println!("{}", debugger.dump()),
+        Some("l") => print!("{}", disassemble(&debugger.cpu)),
+        Some("i") => {
+            println!(
+                "breakpoints: {:?}",
+                debugger.breakpoints().collect::<Vec<_>>()
+            );
+            println!("watchpoints: {:?}", debugger.watches().collect::<Vec<_>>());
+        }
+        Some("q") => return Ok(false),
+        Some(_) => println!("{}", HELP),
+    }
+    Ok(true)
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let path = args
+        .next()
+        .context("usage: elfcode-debug <program> [registers]")?;
+    let registers = match args.next() {
+        Some(n) => n
+            .parse()
+            .with_context(|| format!("bad register count {:?}", n))?,
+        None => Cpu::default().registers.len(),
+    };
+    let source = std::fs::read_to_string(&path).with_context(|| format!("reading {}", path))?;
+    let cpu = Cpu::parse(source.trim_end(), registers, Dialect::Extended)
+        .with_context(|| format!("parsing {}", path))?;
+    let mut debugger = Debugger::new(cpu, 1000);
+
+    let stdin = io::stdin();
+    loop {
+        print!("({}) ", debugger.pc());
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        match command(&mut debugger, &line) {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(e) => println!("error: {:#}", e),
+        }
+    }
+}
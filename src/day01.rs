@@ -1,8 +1,8 @@
 use std::collections::HashSet;
 
 #[aoc_generator(day1)]
-fn generate(s: &str) -> Vec<i32> {
-    s.lines().map(|l| l.parse().unwrap()).collect()
+fn generate(s: &str) -> anyhow::Result<Vec<i32>> {
+    crate::parse::lines(1, s)
 }
 
 #[aoc(day1, part1)]
@@ -1,8 +1,10 @@
+use anyhow::Context;
 use lazy_static::lazy_static;
-use ndarray::prelude::*;
 use regex::Regex;
 
-#[derive(Debug)]
+use crate::grid::{Grid, Pos};
+
+#[derive(Debug, PartialEq)]
 struct Claim {
     id: usize,
     x: usize,
@@ -18,37 +20,62 @@ impl std::str::FromStr for Claim {
         lazy_static! {
             static ref RE: Regex = Regex::new(r"#(\d+) @ (\d+),(\d+): (\d+)x(\d+)").unwrap();
         }
-        let caps = RE.captures(s).unwrap();
+        let caps = RE.captures(s).context("expected \"#id @ x,y: wxh\"")?;
         Ok(Claim {
-            id: caps.get(1).unwrap().as_str().parse()?,
-            x: caps.get(2).unwrap().as_str().parse()?,
-            y: caps.get(3).unwrap().as_str().parse()?,
-            w: caps.get(4).unwrap().as_str().parse()?,
-            h: caps.get(5).unwrap().as_str().parse()?,
+            id: caps[1].parse()?,
+            x: caps[2].parse()?,
+            y: caps[3].parse()?,
+            w: caps[4].parse()?,
+            h: caps[5].parse()?,
         })
     }
 }
 
+impl std::fmt::Display for Claim {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "#{} @ {},{}: {}x{}",
+            self.id, self.x, self.y, self.w, self.h
+        )
+    }
+}
+
 impl Claim {
-    fn region(&self) -> impl ndarray::SliceArg<Ix2> {
-        s![self.x..self.x + self.w, self.y..self.y + self.h]
+    fn region(&self, fabric: &Grid<u32>) -> impl Iterator<Item = Pos> {
+        fabric.rect((self.y, self.x), (self.h, self.w))
     }
 }
 
 #[aoc_generator(day3)]
-fn generate(input: &str) -> Vec<Claim> {
-    input.lines().map(|l| l.parse().unwrap()).collect()
+fn generate(input: &str) -> anyhow::Result<Vec<Claim>> {
+    crate::parse::lines(3, input)
 }
 
-fn mark_fabric(claims: &[Claim]) -> Array2<u32> {
-    let mut fabric: Array2<u32> = Array2::zeros((1000, 1000));
+fn mark_fabric(claims: &[Claim]) -> Grid<u32> {
+    let mut fabric = Grid::new(1000, 1000);
     for claim in claims {
-        let mut region = fabric.slice_mut(claim.region());
-        region += 1;
+        for p in claim.region(&fabric) {
+            fabric[p] += 1;
+        }
     }
     fabric
 }
 
+#[cfg(test)]
+mod round_trip {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn claim(id: usize, x: usize, y: usize, w: usize, h: usize) {
+            crate::parse::round_trips([Claim { id, x, y, w, h }]);
+        }
+    }
+}
+
 #[aoc(day3, part1)]
 fn solve(claims: &[Claim]) -> usize {
     mark_fabric(claims).iter().filter(|&&v| v > 1).count()
@@ -57,15 +84,17 @@ fn solve(claims: &[Claim]) -> usize {
 #[cfg(test)]
 #[test]
 fn test_solve() {
-    assert_eq!(solve(&generate(include_str!("day03_example.txt"))), 4)
+    assert_eq!(
+        solve(&generate(include_str!("day03_example.txt")).unwrap()),
+        4
+    )
 }
 
 #[aoc(day3, part2)]
 fn solve2(claims: &[Claim]) -> usize {
     let fabric = mark_fabric(claims);
     for claim in claims {
-        let region = fabric.slice(claim.region());
-        if region.iter().all(|&v| v == 1) {
+        if claim.region(&fabric).all(|p| fabric[p] == 1) {
             return claim.id;
         }
     }
@@ -75,5 +104,8 @@ fn solve2(claims: &[Claim]) -> usize {
 #[cfg(test)]
 #[test]
 fn test_solve2() {
-    assert_eq!(solve2(&generate(include_str!("day03_example.txt"))), 3)
+    assert_eq!(
+        solve2(&generate(include_str!("day03_example.txt")).unwrap()),
+        3
+    )
 }
@@ -1,8 +1,9 @@
 use std::collections::HashMap;
 
+use anyhow::{bail, Context};
 use itertools::Itertools;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 enum What {
     Guard(usize),
     Sleep,
@@ -14,16 +15,32 @@ impl std::str::FromStr for What {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let chunks = s.split_ascii_whitespace().collect_vec();
-        match chunks[0] {
-            "falls" => Ok(What::Sleep),
-            "wakes" => Ok(What::Wakes),
-            _ => Ok(What::Guard(chunks[1][1..].parse()?)),
+        match chunks[..] {
+            ["falls", ..] => Ok(What::Sleep),
+            ["wakes", ..] => Ok(What::Wakes),
+            ["Guard", id, ..] => Ok(What::Guard(
+                id.strip_prefix('#')
+                    .context("expected guard #id")?
+                    .parse()?,
+            )),
+            _ => bail!("unknown event"),
         }
     }
 }
 
-#[derive(Debug)]
+impl std::fmt::Display for What {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            What::Guard(id) => write!(f, "Guard #{} begins shift", id),
+            What::Sleep => write!(f, "falls asleep"),
+            What::Wakes => write!(f, "wakes up"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
 struct Event {
+    time: String,
     minute: usize,
     what: What,
 }
@@ -32,15 +49,22 @@ impl std::str::FromStr for Event {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let chunks = s.split("] ").collect_vec();
-        let time = chunks[0].split(':').collect_vec();
+        let (time, what) = s.split_once("] ").context("expected \"[time] event\"")?;
+        let (_, minute) = time.split_once(':').context("expected hh:mm")?;
         Ok(Event {
-            minute: time[1].parse()?,
-            what: chunks[1].parse()?,
+            time: time.strip_prefix('[').unwrap_or(time).to_string(),
+            minute: minute.parse()?,
+            what: what.parse()?,
         })
     }
 }
 
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.time, self.what)
+    }
+}
+
 #[derive(Debug)]
 struct Guard {
     minutes: [usize; 60],
@@ -72,8 +96,38 @@ impl Guard {
 }
 
 #[aoc_generator(day4)]
-fn generate(input: &str) -> Vec<Event> {
-    input.lines().sorted().map(|l| l.parse().unwrap()).collect()
+fn generate(input: &str) -> anyhow::Result<Vec<Event>> {
+    input
+        .lines()
+        .enumerate()
+        .sorted_by_key(|&(_, l)| l)
+        .map(|(i, l)| l.parse().with_context(|| crate::parse::at(4, i + 1, l)))
+        .collect()
+}
+
+#[cfg(test)]
+mod round_trip {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn event(
+            month in 1..=12,
+            day in 1..=31,
+            hour in 0..24,
+            minute in 0..60usize,
+            what in prop_oneof![
+                any::<usize>().prop_map(What::Guard),
+                Just(What::Sleep),
+                Just(What::Wakes),
+            ],
+        ) {
+            let time = format!("1518-{:02}-{:02} {:02}:{:02}", month, day, hour, minute);
+            crate::parse::round_trips([Event { time, minute, what }]);
+        }
+    }
 }
 
 fn exploit_weakest_guard(
@@ -103,7 +157,7 @@ fn exploit_weakest_guard(
     guards
         .iter()
         .sorted_by(|(_, av), (_, bv)| weakest(av, bv))
-        .map(|(&k, v)| k * v.common_minute() as usize)
+        .map(|(&k, v)| k * v.common_minute())
         .next()
         .unwrap()
 }
@@ -118,7 +172,10 @@ fn solve(events: &[Event]) -> usize {
 #[cfg(test)]
 #[test]
 fn test_solve() {
-    assert_eq!(solve(&generate(include_str!("day04_example.txt"))), 240)
+    assert_eq!(
+        solve(&generate(include_str!("day04_example.txt")).unwrap()),
+        240
+    )
 }
 
 #[aoc(day4, part2)]
@@ -129,5 +186,19 @@ fn solve2(events: &[Event]) -> usize {
 #[cfg(test)]
 #[test]
 fn test_solve2() {
-    assert_eq!(solve2(&generate(include_str!("day04_example.txt"))), 4455)
+    assert_eq!(
+        solve2(&generate(include_str!("day04_example.txt")).unwrap()),
+        4455
+    )
+}
+
+#[cfg(test)]
+#[test]
+fn test_generate_reports_original_line() {
+    let error =
+        generate("[1518-11-01 00:05] falls asleep\n[1518-11-01 00:00] Guard 10").unwrap_err();
+    assert_eq!(
+        format!("{:#}", error),
+        "day 4 line 2: \"[1518-11-01 00:00] Guard 10\": expected guard #id"
+    );
 }
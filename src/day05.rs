@@ -13,8 +13,7 @@ fn stable_length_original(s: &str) -> usize {
     loop {
         let before = elements.len();
         for i in 0..elements.len() - 1 {
-            if elements[i].to_ascii_lowercase() == elements[i + 1].to_ascii_lowercase()
-                && elements[i] != elements[i + 1]
+            if elements[i].eq_ignore_ascii_case(&elements[i + 1]) && elements[i] != elements[i + 1]
             {
                 elements.remove(i);
                 elements.remove(i);
@@ -32,8 +31,7 @@ fn stable_length_retain(s: &str) -> usize {
     loop {
         let mut i = 0;
         while i < elements.len() - 1 {
-            if elements[i].to_ascii_lowercase() == elements[i + 1].to_ascii_lowercase()
-                && elements[i] != elements[i + 1]
+            if elements[i].eq_ignore_ascii_case(&elements[i + 1]) && elements[i] != elements[i + 1]
             {
                 elements[i] = 0;
                 elements[i + 1] = 0;
@@ -1,8 +1,11 @@
 use std::collections::HashSet;
 
+use anyhow::Context;
 use itertools::{iproduct, Itertools};
-use ndarray::prelude::*;
 
+use crate::grid::Grid;
+
+#[derive(Debug, PartialEq)]
 struct Point {
     x: usize,
     y: usize,
@@ -12,7 +15,7 @@ impl std::str::FromStr for Point {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (x, y) = s.split_once(", ").unwrap();
+        let (x, y) = s.split_once(", ").context("expected \"x, y\"")?;
         Ok(Self {
             x: x.parse()?,
             y: y.parse()?,
@@ -20,6 +23,12 @@ impl std::str::FromStr for Point {
     }
 }
 
+impl std::fmt::Display for Point {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}, {}", self.x, self.y)
+    }
+}
+
 impl Point {
     fn distance_to(&self, x: usize, y: usize) -> usize {
         x.abs_diff(self.x) + y.abs_diff(self.y)
@@ -27,8 +36,22 @@ impl Point {
 }
 
 #[aoc_generator(day6)]
-fn generate(input: &str) -> Vec<Point> {
-    input.lines().map(|l| l.parse().unwrap()).collect()
+fn generate(input: &str) -> anyhow::Result<Vec<Point>> {
+    crate::parse::lines(6, input)
+}
+
+#[cfg(test)]
+mod round_trip {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn point(x: usize, y: usize) {
+            crate::parse::round_trips([Point { x, y }]);
+        }
+    }
 }
 
 #[aoc(day6, part1)]
@@ -38,26 +61,24 @@ fn solve(points: &[Point]) -> usize {
     let max_y = points.iter().map(|p| p.y).max().unwrap();
     let padding = 2;
 
-    let mut zone: Array2<usize> = Array::zeros((max_x + padding, max_y + padding)).reversed_axes();
-    for ((x, y), cell) in zone.indexed_iter_mut() {
+    let zone = Grid::from_fn(max_y + padding, max_x + padding, |(y, x)| {
         let distances = points.iter().map(|p| p.distance_to(x, y)).collect_vec();
         let min = distances.iter().min().unwrap();
         let with_min = distances.iter().positions(|d| d == min).collect_vec();
         if with_min.len() == 1 {
-            *cell = with_min[0] + 1
+            with_min[0] + 1
+        } else {
+            0
         }
-    }
+    });
 
     // Use 'is on the edge' as a proxy for 'infinite'
-    let infinite: HashSet<usize> = [
-        zone.row(0).to_vec(),
-        zone.column(0).to_vec(),
-        zone.row(zone.dim().0 - 1).to_vec(),
-        zone.column(zone.dim().1 - 1).to_vec(),
-    ]
-    .concat()
-    .into_iter()
-    .collect();
+    let (bottom, right) = (zone.height() - 1, zone.width() - 1);
+    let infinite: HashSet<usize> = zone
+        .indexed()
+        .filter(|&((y, x), _)| y == 0 || x == 0 || y == bottom || x == right)
+        .map(|(_, &c)| c)
+        .collect();
 
     // All the finite areas
     let areas = zone.iter().filter(|&c| !infinite.contains(c)).counts();
@@ -67,7 +88,10 @@ fn solve(points: &[Point]) -> usize {
 #[cfg(test)]
 #[test]
 fn test_solve() {
-    assert_eq!(solve(&generate(include_str!("day06_example.txt"))), 17)
+    assert_eq!(
+        solve(&generate(include_str!("day06_example.txt")).unwrap()),
+        17
+    )
 }
 
 #[aoc(day6, part2)]
@@ -92,7 +116,7 @@ fn region_sum_below(limit: usize, points: &[Point]) -> usize {
 #[test]
 fn test_region_sum_below() {
     assert_eq!(
-        region_sum_below(32, &generate(include_str!("day06_example.txt"))),
+        region_sum_below(32, &generate(include_str!("day06_example.txt")).unwrap()),
         16
     )
 }
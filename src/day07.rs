@@ -1,10 +1,11 @@
 use std::collections::{HashMap, HashSet};
 
+use anyhow::Context;
 use itertools::Itertools;
 
 type Node = char;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 struct Edge(Node, Node);
 
 impl std::str::FromStr for Edge {
@@ -12,14 +13,26 @@ impl std::str::FromStr for Edge {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let toks = s.split_ascii_whitespace().collect_vec();
-        Ok(Edge(
-            toks[1].chars().next().unwrap(),
-            toks[7].chars().next().unwrap(),
-        ))
+        let step = |i: usize| {
+            toks.get(i)
+                .and_then(|t| t.chars().next())
+                .with_context(|| format!("missing step in word {}", i + 1))
+        };
+        Ok(Edge(step(1)?, step(7)?))
     }
 }
 
-#[derive(Debug)]
+impl std::fmt::Display for Edge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Step {} must be finished before step {} can begin.",
+            self.0, self.1
+        )
+    }
+}
+
+#[derive(Debug, PartialEq)]
 struct Graph {
     edges: Vec<Edge>,
 }
@@ -28,11 +41,20 @@ impl std::str::FromStr for Graph {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let edges = s.lines().map(|l| l.parse().unwrap()).collect::<Vec<Edge>>();
+        let edges = crate::parse::lines(7, s)?;
         Ok(Graph { edges })
     }
 }
 
+impl std::fmt::Display for Graph {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for edge in &self.edges {
+            writeln!(f, "{}", edge)?;
+        }
+        Ok(())
+    }
+}
+
 impl Graph {
     fn lexical_topological(&self) -> Vec<Node> {
         let mut deps: HashMap<Node, HashSet<Node>> = HashMap::new();
@@ -118,8 +140,32 @@ impl Graph {
 }
 
 #[aoc_generator(day7)]
-fn generate(input: &str) -> Graph {
-    input.parse().unwrap()
+fn generate(input: &str) -> anyhow::Result<Graph> {
+    input.parse()
+}
+
+#[cfg(test)]
+mod round_trip {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        // Steps are the first character of their word, so anything but whitespace.
+        #[test]
+        fn graph(
+            edges in prop::collection::vec(
+                any::<(Node, Node)>()
+                    .prop_filter("steps are words", |(a, b)| {
+                        !a.is_ascii_whitespace() && !b.is_ascii_whitespace()
+                    }),
+                0..30,
+            ),
+        ) {
+            let edges = edges.into_iter().map(|(a, b)| Edge(a, b)).collect();
+            crate::parse::round_trips([Graph { edges }]);
+        }
+    }
 }
 
 #[aoc(day7, part1)]
@@ -131,7 +177,7 @@ fn solve(graph: &Graph) -> String {
 #[test]
 fn test_solve() {
     assert_eq!(
-        solve(&generate(include_str!("day07_example.txt"))),
+        solve(&generate(include_str!("day07_example.txt")).unwrap()),
         "CABDFE"
     )
 }
@@ -145,7 +191,9 @@ fn solve2(graph: &Graph) -> usize {
 #[test]
 fn test_solve2() {
     assert_eq!(
-        generate(include_str!("day07_example.txt")).lexical_topological_scheduler(2, 0),
+        generate(include_str!("day07_example.txt"))
+            .unwrap()
+            .lexical_topological_scheduler(2, 0),
         15
     );
 }
@@ -1,3 +1,5 @@
+use anyhow::Context;
+
 type Value = usize;
 
 #[derive(Debug, Default)]
@@ -30,31 +32,33 @@ impl Node {
     }
 }
 
-fn parse_node(values: &[Value]) -> (Node, usize) {
+fn parse_node(values: &[Value]) -> Option<(Node, usize)> {
     let mut offset = 2;
     let mut children = vec![];
-    for _ in 0..values[0] {
-        let (kid, size) = parse_node(&values[offset..]);
+    for _ in 0..*values.first()? {
+        let (kid, size) = parse_node(values.get(offset..)?)?;
         children.push(kid);
         offset += size;
     }
     let mut metadata = vec![];
-    for _ in 0..values[1] {
-        metadata.push(values[offset]);
+    for _ in 0..*values.get(1)? {
+        metadata.push(*values.get(offset)?);
         offset += 1;
     }
 
-    (Node { children, metadata }, offset)
+    Some((Node { children, metadata }, offset))
 }
 
 #[aoc_generator(day8)]
-fn generate(input: &str) -> Node {
-    let values: Vec<Value> = input
-        .split_ascii_whitespace()
-        .map(|l| l.parse().unwrap())
-        .collect();
-    let (node, _size) = parse_node(&values);
-    node
+fn generate(input: &str) -> anyhow::Result<Node> {
+    let values: Vec<Value> = crate::parse::lines_with(8, input, |l| {
+        l.split_ascii_whitespace()
+            .map(|v| v.parse())
+            .collect::<Result<Vec<Value>, _>>()
+    })?
+    .concat();
+    let (node, _size) = parse_node(&values).context("day 8: tree is truncated")?;
+    Ok(node)
 }
 
 #[aoc(day8, part1)]
@@ -65,7 +69,10 @@ fn solve(root: &Node) -> Value {
 #[cfg(test)]
 #[test]
 fn test_solve() {
-    assert_eq!(solve(&generate("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2")), 138)
+    assert_eq!(
+        solve(&generate("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2").unwrap()),
+        138
+    )
 }
 
 #[aoc(day8, part2)]
@@ -76,5 +83,8 @@ fn solve2(root: &Node) -> Value {
 #[cfg(test)]
 #[test]
 fn test_solve2() {
-    assert_eq!(solve2(&generate("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2")), 66)
+    assert_eq!(
+        solve2(&generate("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2").unwrap()),
+        66
+    )
 }
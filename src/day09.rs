@@ -1,12 +1,18 @@
 use std::collections::VecDeque;
 
+use anyhow::{bail, Context};
+
 #[aoc_generator(day9)]
-fn generate(s: &str) -> [usize; 2] {
-    let chunks: Vec<_> = s.split_ascii_whitespace().collect();
-    let players = chunks[0].parse().unwrap();
-    let last_marble = chunks[6].parse().unwrap();
+fn generate(s: &str) -> anyhow::Result<[usize; 2]> {
+    let games = crate::parse::lines_with(9, s, |l| -> anyhow::Result<_> {
+        let chunks: Vec<_> = l.split_ascii_whitespace().collect();
+        let [players, .., last_marble, _] = chunks[..] else {
+            bail!("expected \"N players; last marble is worth M points\"");
+        };
 
-    [players, last_marble]
+        Ok([players.parse()?, last_marble.parse()?])
+    })?;
+    games.into_iter().next().context("day 9: empty input")
 }
 
 #[aoc(day9, part1)]
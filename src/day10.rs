@@ -1,17 +1,19 @@
+use anyhow::Context;
 use itertools::{Itertools, MinMaxResult};
 use lazy_static::lazy_static;
-use pathfinding::grid::Grid;
 use regex::Regex;
 
+use crate::grid::SparseGrid;
+
 type Number = i64;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 struct Point {
     x: Number,
     y: Number,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 struct Observation {
     position: Point,
     vector: Point,
@@ -25,20 +27,32 @@ impl std::str::FromStr for Observation {
             static ref RE: Regex =
                 Regex::new(r"position=<(.*?), (.*?)> velocity=<(.*?), (.*?)>").unwrap();
         }
-        let caps = RE.captures(s).unwrap();
+        let caps = RE
+            .captures(s)
+            .context("expected \"position=<x, y> velocity=<dx, dy>\"")?;
         Ok(Observation {
             position: Point {
-                x: caps.get(1).unwrap().as_str().trim().parse()?,
-                y: caps.get(2).unwrap().as_str().trim().parse()?,
+                x: caps[1].trim().parse()?,
+                y: caps[2].trim().parse()?,
             },
             vector: Point {
-                x: caps.get(3).unwrap().as_str().trim().parse()?,
-                y: caps.get(4).unwrap().as_str().trim().parse()?,
+                x: caps[3].trim().parse()?,
+                y: caps[4].trim().parse()?,
             },
         })
     }
 }
 
+impl std::fmt::Display for Observation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "position=<{:>6}, {:>6}> velocity=<{:>2}, {:>2}>",
+            self.position.x, self.position.y, self.vector.x, self.vector.y
+        )
+    }
+}
+
 fn points_at(time: Number, sky: &[Observation]) -> Vec<Point> {
     sky.iter()
         .map(|o| Point {
@@ -64,7 +78,6 @@ fn area(points: &[Point]) -> Number {
 
 fn smallest_area(sky: &[Observation]) -> (Number, Vec<Point>) {
     (1..15_000)
-        .into_iter()
         .map(|time| (time, points_at(time, sky)))
         .sorted_by(|a, b| Ord::cmp(&area(&a.1), &area(&b.1)))
         .next()
@@ -72,26 +85,37 @@ fn smallest_area(sky: &[Observation]) -> (Number, Vec<Point>) {
 }
 
 fn render(points: &[Point]) -> String {
-    let min_x = points.iter().map(|p| p.x).min().unwrap();
-    let min_y = points.iter().map(|p| p.y).min().unwrap();
-    let grid = Grid::from_iter(
-        points
-            .iter()
-            .map(|p| ((p.x - min_x) as usize, (p.y - min_y) as usize)),
-    );
-    format!("{:?}", grid).replace('.', " ")
+    let sky = SparseGrid::from_iter(points.iter().map(|p| ((p.y, p.x), '#')));
+    sky.to_string().trim_end_matches('\n').to_string()
 }
 
 #[cfg(test)]
 #[test]
 fn test_smallest_area() {
-    let (time, _) = smallest_area(&generate(include_str!("day10_example.txt")));
+    let (time, _) = smallest_area(&generate(include_str!("day10_example.txt")).unwrap());
     assert_eq!(time, 3);
 }
 
 #[aoc_generator(day10)]
-fn generate(input: &str) -> Vec<Observation> {
-    input.lines().map(|l| l.parse().unwrap()).collect()
+fn generate(input: &str) -> anyhow::Result<Vec<Observation>> {
+    crate::parse::lines(10, input)
+}
+
+#[cfg(test)]
+mod round_trip {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn observation(x: Number, y: Number, dx: Number, dy: Number) {
+            crate::parse::round_trips([Observation {
+                position: Point { x, y },
+                vector: Point { x: dx, y: dy },
+            }]);
+        }
+    }
 }
 
 #[aoc(day10, part1)]
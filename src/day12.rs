@@ -1,21 +1,37 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::HashMap;
 
+use anyhow::{anyhow, Context};
 use itertools::Itertools;
 
-#[derive(Clone)]
+use crate::simulation::{find_cycle, run_for, Cycle, Simulation};
+
+#[derive(Clone, PartialEq)]
 struct Plants {
-    steps: usize,
+    // The number of the pot at the start of `pots`.
+    first: i64,
     pots: Vec<bool>,
     rules: HashMap<[bool; 5], bool>,
 }
 
+fn pots(pots: &[bool]) -> String {
+    String::from_iter(pots.iter().map(|&p| if p { '#' } else { '.' }))
+}
+
 impl std::fmt::Debug for Plants {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            String::from_iter(self.pots.iter().map(|&p| if p { '#' } else { '.' }))
-        )
+        write!(f, "{}", pots(&self.pots))
+    }
+}
+
+// Later generations print as a fresh initial state, numbered from zero again.
+impl std::fmt::Display for Plants {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "initial state: {}", pots(&self.pots))?;
+        writeln!(f)?;
+        for (pattern, &state) in self.rules.iter().sorted() {
+            writeln!(f, "{} => {}", pots(pattern), pots(&[state]))?;
+        }
+        Ok(())
     }
 }
 
@@ -23,94 +39,142 @@ impl std::str::FromStr for Plants {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut lines = s.lines();
-        let start = lines.next().unwrap();
-        let (_, pots) = start.split_once(": ").unwrap();
+        let (start, rules) = s.split_once("\n\n").context("day 12: missing rules")?;
+        let (_, pots) = start
+            .split_once(": ")
+            .with_context(|| crate::parse::at(12, 1, start))?;
         let pots = pots.chars().map(|c| c == '#').collect();
-        let rules: HashMap<[bool; 5], bool> = lines
-            .skip(1)
-            .map(|l| {
-                let (pattern, state) = l.split_once(" => ").unwrap();
-                (
-                    pattern
-                        .chars()
-                        .map(|c| c == '#')
-                        .collect_vec()
-                        .try_into()
-                        .unwrap(),
-                    state.starts_with('#'),
-                )
-            })
-            .collect();
+        let rules: HashMap<[bool; 5], bool> = crate::parse::lines_from(12, 3, rules, |l| {
+            let (pattern, state) = l.split_once(" => ").context("expected \"LLCRR => N\"")?;
+            let pattern: [bool; 5] = pattern
+                .chars()
+                .map(|c| c == '#')
+                .collect_vec()
+                .try_into()
+                .map_err(|_| anyhow!("pattern must be 5 pots"))?;
+            anyhow::Ok((pattern, state.starts_with('#')))
+        })?
+        .into_iter()
+        .collect();
 
-        let steps = 0;
-        Ok(Plants { steps, pots, rules })
+        Ok(Plants {
+            first: 0,
+            pots,
+            rules,
+        })
     }
 }
 
-impl Plants {
+impl Simulation for Plants {
+    // The live pots with the empty ends trimmed, wherever they have drifted to.
+    type Key = Vec<bool>;
+
+    // A pot can only take after its neighbours two either side, so the row
+    // grows by at most two pots each way.
     fn step(&mut self) {
-        let pots = [&[false; 3][..], &self.pots, &[false; 3][..]].concat();
+        let pots = [&[false; 4][..], &self.pots, &[false; 4][..]].concat();
         let next = pots
             .windows(5)
             .map(|w| match self.rules.get(w) {
                 Some(&x) => x,
                 None => false,
             })
-            .collect();
-        self.steps += 1;
-        self.pots = next;
+            .collect_vec();
+        let start = next.iter().position(|&p| p).unwrap_or(next.len());
+        let end = next.iter().rposition(|&p| p).map_or(start, |i| i + 1);
+        self.first += start as i64 - 2;
+        self.pots = next[start..end].to_vec();
     }
 
+    fn state_key(&self) -> Vec<bool> {
+        let start = self.pots.iter().position(|&p| p).unwrap_or(0);
+        let end = self.pots.iter().rposition(|&p| p).map_or(0, |i| i + 1);
+        self.pots[start..end].to_vec()
+    }
+
+    fn render(&self) -> String {
+        pots(&self.pots)
+    }
+}
+
+impl Plants {
     fn score(&self) -> i64 {
         self.pots
             .iter()
             .enumerate()
-            .map(|(i, &p)| if p { i as i64 - self.steps as i64 } else { 0 })
+            .map(|(i, &p)| if p { self.first + i as i64 } else { 0 })
             .sum()
     }
 }
 
 #[aoc_generator(day12)]
-fn generate(input: &str) -> Plants {
-    input.parse().unwrap()
+fn generate(input: &str) -> anyhow::Result<Plants> {
+    input.parse()
+}
+
+#[cfg(test)]
+mod round_trip {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        // Parsing always numbers the first pot zero.
+        #[test]
+        fn plants(
+            pots in prop::collection::vec(any::<bool>(), 0..50),
+            rules in prop::collection::hash_map(
+                any::<[bool; 5]>(),
+                any::<bool>(),
+                0..=32,
+            ),
+        ) {
+            crate::parse::round_trips([Plants { first: 0, pots, rules }]);
+        }
+    }
+}
+
+// Once the same pattern comes round again it repeats every period, shifted
+// along by the same number of pots, so each period adds the same score.
+fn score_after(plants: &Plants, goal: usize) -> i64 {
+    let mut plants = plants.clone();
+    let Some(Cycle { start, period }) = find_cycle(&mut plants, goal) else {
+        return plants.score();
+    };
+    let left = goal - (start + period);
+    let mut ahead = plants.clone();
+    run_for(&mut ahead, period);
+    let drift = ahead.score() - plants.score();
+    run_for(&mut plants, left % period);
+    plants.score() + (left / period) as i64 * drift
 }
 
 #[aoc(day12, part1)]
 fn solve(plants: &Plants) -> i64 {
-    let mut plants = plants.clone();
-    // println!("{:?}", plants);
-    for _ in 0..20 {
-        plants.step();
-        // println!("{:?}", plants);
-    }
-    plants.score()
+    score_after(plants, 20)
 }
 
 #[cfg(test)]
 #[test]
 fn test_solve() {
-    assert_eq!(solve(&generate(include_str!("day12_example.txt"))), 325);
+    assert_eq!(
+        solve(&generate(include_str!("day12_example.txt")).unwrap()),
+        325
+    );
 }
 
 #[aoc(day12, part2)]
 fn solve2(plants: &Plants) -> i64 {
-    let mut plants = plants.clone();
-    let goal = 50_000_000_000;
-    let mut diffs = VecDeque::from([0; 5]);
-    let mut last = plants.score();
-    for step in 1.. {
+    score_after(plants, 50_000_000_000)
+}
+
+#[cfg(test)]
+#[test]
+fn test_score_after_matches_stepping() {
+    let start = generate(include_str!("day12_example.txt")).unwrap();
+    let mut plants = start.clone();
+    for goal in 0..100 {
+        assert_eq!(score_after(&start, goal), plants.score(), "after {}", goal);
         plants.step();
-        let score = plants.score();
-        let diff = score - last;
-        last = score;
-        diffs.pop_front();
-        diffs.push_back(diff);
-        if diffs.iter().all(|&x| x == diff) {
-            // We've stabilised into a linear relationship,
-            // so now we can just calculate
-            return (goal - step) * diff + score;
-        }
     }
-    unreachable!()
 }
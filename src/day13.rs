@@ -1,9 +1,14 @@
 use std::collections::{HashMap, HashSet};
 
+use anyhow::Context;
 use itertools::Itertools;
-use pathfinding::matrix::*;
 
-#[derive(Debug, Clone, Copy)]
+use crate::{
+    grid::{self, Grid, Offset, Pos},
+    simulation::{run_to_end, run_until, Simulation},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum Direction {
     Up,
     Down,
@@ -33,6 +38,16 @@ impl Direction {
         }
     }
 
+    fn offset(&self) -> Offset {
+        use Direction::*;
+        match self {
+            Up => grid::UP,
+            Down => grid::DOWN,
+            Left => grid::LEFT,
+            Right => grid::RIGHT,
+        }
+    }
+
     fn turn_right(&self) -> Self {
         use Direction::*;
         match &self {
@@ -61,7 +76,7 @@ impl std::fmt::Display for Direction {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct Cart {
     id: usize,
     row: usize,
@@ -81,35 +96,36 @@ impl Cart {
         }
     }
 
-    fn position(&self) -> (usize, usize) {
+    fn position(&self) -> Pos {
         (self.row, self.col)
     }
 
-    fn set_position(&mut self, (row, col): (usize, usize)) {
+    fn set_position(&mut self, (row, col): Pos) {
         self.row = row;
         self.col = col;
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 struct Railway {
-    map: Matrix<char>,
+    map: Grid<char>,
     carts: Vec<Cart>,
+    crashes: Vec<Pos>,
 }
 
 impl std::str::FromStr for Railway {
     type Err = anyhow::Error;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        let mut map = Matrix::from_rows(input.lines().map(|l| l.chars()))?;
+        let mut map = Grid::parse(13, input, anyhow::Ok)?;
         let mut id = 0;
         let carts = map
-            .indices()
-            .filter_map(|(row, col)| match map.get((row, col)) {
-                Some(&c) if "<>v^".contains(c) => {
+            .positions()
+            .filter_map(|(row, col)| match map[(row, col)] {
+                c if "<>v^".contains(c) => {
                     id += 1;
                     let cart = Some(Cart::new(id, row, col, Direction::new(c)));
-                    *map.get_mut((row, col)).unwrap() = match c {
+                    map[(row, col)] = match c {
                         '<' | '>' => '-',
                         'v' | '^' => '|',
                         _ => unreachable!(),
@@ -119,35 +135,29 @@ impl std::str::FromStr for Railway {
                 _ => None,
             })
             .collect();
-        Ok(Self { map, carts })
+        Ok(Self {
+            map,
+            carts,
+            crashes: Vec::new(),
+        })
     }
 }
 
 impl std::fmt::Display for Railway {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let carts: HashMap<(usize, usize), Direction> = self
-            .carts
-            .iter()
-            .map(|c| (c.position(), c.facing))
-            .collect();
-        for (row, data) in self.map.iter().enumerate() {
-            for (col, c) in data.iter().enumerate() {
-                if let Some(cart) = carts.get(&(row, col)) {
-                    write!(f, "{}", cart)?;
-                } else {
-                    write!(f, "{}", c)?;
-                }
-            }
-            writeln!(f)?;
+        let mut map = self.map.map(char::to_string);
+        for cart in &self.carts {
+            map[cart.position()] = cart.facing.to_string();
         }
-        Ok(())
+        write!(f, "{}", map)
     }
 }
 
-impl Railway {
-    fn tick(&mut self) -> Option<(usize, usize)> {
-        let mut crash = None;
-        let mut positions: HashMap<(usize, usize), usize> =
+impl Simulation for Railway {
+    type Key = Vec<Cart>;
+
+    fn step(&mut self) {
+        let mut positions: HashMap<Pos, usize> =
             self.carts.iter().map(|c| (c.position(), c.id)).collect();
         let mut crashed = HashSet::new();
         self.carts
@@ -155,36 +165,34 @@ impl Railway {
             .sorted_by_key(|c| c.row)
             .for_each(|cart| {
                 use Direction::*;
-                let next = match &cart.facing {
-                    Up => (cart.row - 1, cart.col),
-                    Down => (cart.row + 1, cart.col),
-                    Left => (cart.row, cart.col - 1),
-                    Right => (cart.row, cart.col + 1),
-                };
+                let next = self
+                    .map
+                    .offset(cart.position(), cart.facing.offset())
+                    .expect("cart ran off the map");
 
                 if let Some(&id) = positions.get(&next) {
                     crashed.insert(id);
                     crashed.insert(cart.id);
-                    crash = Some(next);
+                    self.crashes.push(next);
                 }
                 positions.remove(&cart.position());
                 positions.insert(next, cart.id);
                 cart.set_position(next);
 
-                cart.facing = match self.map.get(cart.position()) {
-                    Some('/') => match &cart.facing {
+                cart.facing = match self.map[cart.position()] {
+                    '/' => match &cart.facing {
                         Up => Right,
                         Down => Left,
                         Left => Down,
                         Right => Up,
                     },
-                    Some('\\') => match &cart.facing {
+                    '\\' => match &cart.facing {
                         Up => Left,
                         Down => Right,
                         Left => Up,
                         Right => Down,
                     },
-                    Some('+') => {
+                    '+' => {
                         cart.turned += 1;
                         cart.turned %= 3;
                         match &cart.turned {
@@ -199,51 +207,94 @@ impl Railway {
             });
 
         self.carts.retain(|cart| !crashed.contains(&cart.id));
-        crash
+    }
+
+    fn is_finished(&self) -> bool {
+        self.carts.len() <= 1
+    }
+
+    fn state_key(&self) -> Vec<Cart> {
+        self.carts.clone()
+    }
+
+    fn render(&self) -> String {
+        self.to_string()
     }
 }
 
 #[aoc_generator(day13)]
-fn generate(s: &str) -> Railway {
-    s.parse().unwrap()
+fn generate(s: &str) -> anyhow::Result<Railway> {
+    s.parse()
 }
 
-#[aoc(day13, part1)]
-fn first_crash(railway: &Railway) -> String {
-    let mut railway = (*railway).clone();
-    loop {
-        if let Some(location) = railway.tick() {
-            return format!("{},{}", location.1, location.0);
+#[cfg(test)]
+mod round_trip {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    const TILES: [char; 10] = [' ', '-', '|', '/', '\\', '+', '<', '>', '^', 'v'];
+
+    proptest! {
+        // Carts only exist by being parsed off the track they stand on.
+        #[test]
+        fn railway(
+            rows in crate::parse::rows(prop::sample::select(&TILES[..]), 8),
+        ) {
+            let text = rows.iter().map(String::from_iter).join("\n");
+            crate::parse::round_trips([generate(&text).unwrap()]);
         }
     }
 }
 
+#[aoc(day13, part1)]
+fn first_crash(railway: &Railway) -> anyhow::Result<String> {
+    let mut railway = (*railway).clone();
+    run_until(&mut railway, |r| !r.crashes.is_empty());
+    let location = railway
+        .crashes
+        .first()
+        .context("day 13: the carts never crash")?;
+    Ok(format!("{},{}", location.1, location.0))
+}
+
 #[cfg(test)]
 #[test]
 fn test_first_crash() {
     assert_eq!(
-        first_crash(&generate(include_str!("day13_example.txt"))),
+        first_crash(&generate(include_str!("day13_example.txt")).unwrap()).unwrap(),
         "7,3"
     )
 }
 
 #[aoc(day13, part2)]
-fn survivor_cart(railway: &Railway) -> String {
+fn survivor_cart(railway: &Railway) -> anyhow::Result<String> {
     let mut railway = (*railway).clone();
-    loop {
-        railway.tick();
-        if railway.carts.len() == 1 {
-            let location = railway.carts[0].position();
-            return format!("{},{}", location.1, location.0);
-        }
-    }
+    run_to_end(&mut railway);
+    let location = railway
+        .carts
+        .first()
+        .context("day 13: no cart survives")?
+        .position();
+    Ok(format!("{},{}", location.1, location.0))
 }
 
 #[cfg(test)]
 #[test]
 fn test_survivor_cart() {
     assert_eq!(
-        survivor_cart(&generate(include_str!("day13_example2.txt"))),
+        survivor_cart(&generate(include_str!("day13_example2.txt")).unwrap()).unwrap(),
         "6,4"
     )
 }
+
+#[cfg(test)]
+#[test]
+fn test_head_on_frames() {
+    let mut railway = generate("->-<-").unwrap();
+    assert_eq!(
+        crate::simulation::frames(&mut railway, 5),
+        vec!["->-<-\n", "-----\n"]
+    );
+    assert_eq!(railway.crashes, vec![(0, 2)]);
+}
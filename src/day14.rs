@@ -1,8 +1,17 @@
+use anyhow::Context;
 use itertools::Itertools;
 
+#[aoc_generator(day14, part1)]
+fn generate(input: &str) -> anyhow::Result<usize> {
+    input
+        .trim()
+        .parse()
+        .with_context(|| crate::parse::at(14, 1, input))
+}
+
 #[aoc(day14, part1)]
-fn solve(input: &str) -> String {
-    score_next_ten(input.parse().unwrap())
+fn solve(&count: &usize) -> String {
+    score_next_ten(count)
 }
 
 #[allow(dead_code)] // sometimes used
@@ -32,7 +41,7 @@ fn score_next_ten(count: usize) -> String {
     let mut two = 1;
 
     for _ in 0..count + 10 {
-        let sum = digits[one as usize] + digits[two as usize];
+        let sum = digits[one] + digits[two];
         digits.append(
             &mut format!("{sum}")
                 .chars()
@@ -70,7 +79,7 @@ fn find_run(needle: &[u8]) -> usize {
     let mut two = 1;
 
     loop {
-        let sum = digits[one as usize] + digits[two as usize];
+        let sum = digits[one] + digits[two];
         digits.append(
             &mut format!("{sum}")
                 .chars()
@@ -108,12 +117,21 @@ fn _find_run(s: &str) -> usize {
     )
 }
 
+#[aoc_generator(day14, part2)]
+fn generate2(input: &str) -> anyhow::Result<Vec<u8>> {
+    input
+        .trim()
+        .chars()
+        .map(|c| {
+            c.to_digit(10)
+                .map(|d| d as u8)
+                .with_context(|| format!("bad digit {:?}", c))
+        })
+        .collect::<anyhow::Result<_>>()
+        .with_context(|| crate::parse::at(14, 1, input))
+}
+
 #[aoc(day14, part2)]
-fn solve2(input: &str) -> usize {
-    find_run(
-        &input
-            .chars()
-            .map(|c| c.to_digit(10).unwrap() as u8)
-            .collect_vec(),
-    )
+fn solve2(digits: &[u8]) -> usize {
+    find_run(digits)
 }
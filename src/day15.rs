@@ -1,17 +1,23 @@
 use std::{cmp::Ordering, collections::HashMap};
 
+use anyhow::bail;
 use itertools::{Either, Itertools};
-use pathfinding::prelude::{build_path, dijkstra_all, Matrix};
+use pathfinding::prelude::{build_path, dijkstra_all};
+
+use crate::{
+    grid::{Grid, Pos, ORTHOGONAL},
+    simulation::{run_to_end, run_until, Simulation},
+};
 
 type Health = u8;
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 enum Force {
     Goblin,
     Elf,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 struct Unit {
     force: Force,
     health: Health,
@@ -54,22 +60,25 @@ impl std::fmt::Debug for Unit {
     }
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 enum Cell {
     Empty,
     Wall,
     Mob(Unit),
 }
 
-impl Cell {
-    fn new(c: char) -> Self {
+impl TryFrom<char> for Cell {
+    type Error = anyhow::Error;
+
+    fn try_from(c: char) -> Result<Self, Self::Error> {
         use Cell::*;
         use Force::*;
         match c {
-            '#' => Wall,
-            'G' => Mob(Unit::new(Goblin, 200)),
-            'E' => Mob(Unit::new(Elf, 200)),
-            _ => Empty,
+            '#' => Ok(Wall),
+            'G' => Ok(Mob(Unit::new(Goblin, 200))),
+            'E' => Ok(Mob(Unit::new(Elf, 200))),
+            '.' => Ok(Empty),
+            _ => bail!("bad character {:?}", c),
         }
     }
 }
@@ -98,8 +107,10 @@ impl std::fmt::Debug for Cell {
 
 #[derive(Clone, PartialEq)]
 struct Game {
-    map: Matrix<Cell>,
+    map: Grid<Cell>,
     round: usize,
+    // Extra attack power for the elves, on top of everyone's 3.
+    elfbuff: u8,
 }
 
 impl std::str::FromStr for Game {
@@ -108,7 +119,8 @@ impl std::str::FromStr for Game {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         Ok(Game {
             round: 0,
-            map: Matrix::from_iter(s.lines().map(|l| l.chars().map(Cell::new))),
+            map: Grid::parse(15, s, Cell::try_from)?,
+            elfbuff: 0,
         })
     }
 }
@@ -117,7 +129,7 @@ impl std::fmt::Display for Game {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         use Cell::*;
         writeln!(f, "Round {}", self.round)?;
-        for row in self.map.iter() {
+        for row in self.map.rows() {
             let mut mobs = vec![];
             for col in row {
                 write!(f, "{}", col)?;
@@ -150,7 +162,7 @@ impl Game {
         use Force::*;
         let (elves, goblins): (Vec<_>, Vec<_>) = self
             .map
-            .values()
+            .iter()
             .filter(|c| matches!(c, Mob(_)))
             .partition_map(|&cell| match cell {
                 Mob(unit) if unit.force == Elf => Either::Left(unit.health),
@@ -168,13 +180,13 @@ impl Game {
 
     fn elves(&self) -> usize {
         self.map
-            .values()
+            .iter()
             .filter(|c| matches!(c, Cell::Mob(mob) if mob.force == Force::Elf))
             .count()
     }
 
     fn is_over(&self) -> bool {
-        matches!(self.winning_team(), Some(_))
+        self.winning_team().is_some()
     }
 
     fn score(&self) -> usize {
@@ -196,47 +208,45 @@ impl Game {
         }
     }
 
-    fn adjacent_enemies(&self, location: (usize, usize), force: Force) -> Vec<(usize, usize)> {
+    fn adjacent_enemies(&self, location: Pos, force: Force) -> Vec<Pos> {
         self.map
-                .neighbours(location, false)
-                .filter(|&cell| matches!(self.map.get(cell), Some(&Cell::Mob(other)) if other.force != force))
-                .collect()
+            .neighbours(location)
+            .filter(|&cell| matches!(self.map[cell], Cell::Mob(other) if other.force != force))
+            .collect()
     }
 
-    fn successors_costed(&self, (row, col): (usize, usize)) -> Vec<((usize, usize), usize)> {
-        [
-            ((row - 1, col), 1), // Up
-            ((row, col - 1), 2), // Left
-            ((row, col + 1), 3), // Right
-            ((row + 1, col), 4), // Down
-        ]
-        .into_iter()
-        .filter(|&(p, _)| matches!(self.map.get(p), Some(Cell::Empty)))
-        .collect()
+    fn successors_costed(&self, location: Pos) -> Vec<(Pos, usize)> {
+        // Up, Left, Right, Down
+        ORTHOGONAL
+            .into_iter()
+            .zip(1..)
+            .filter_map(|(offset, cost)| Some((self.map.offset(location, offset)?, cost)))
+            .filter(|&(p, _)| self.map[p] == Cell::Empty)
+            .collect()
     }
 
-    fn reading_order(p1: (usize, usize), p2: (usize, usize)) -> Ordering {
+    fn reading_order(p1: Pos, p2: Pos) -> Ordering {
         match Ord::cmp(&p1.0, &p2.0) {
             Ordering::Equal => Ord::cmp(&p1.1, &p2.1),
             ord => ord,
         }
     }
 
-    fn unit_at(&self, p: (usize, usize)) -> Unit {
-        match self.map.get(p) {
-            Some(&Cell::Mob(mob)) => mob,
+    fn unit_at(&self, p: Pos) -> Unit {
+        match self.map[p] {
+            Cell::Mob(mob) => mob,
             _ => unreachable!(),
         }
     }
 
-    fn move_for(&self, location: (usize, usize)) -> Option<(usize, usize)> {
+    fn move_for(&self, location: Pos) -> Option<Pos> {
         let mob = self.unit_at(location);
         if !self.adjacent_enemies(location, mob.force).is_empty() {
             // No need to move, we're in striking range
             return None;
         }
 
-        let reachable: HashMap<(usize, usize), ((usize, usize), usize)> =
+        let reachable: HashMap<Pos, (Pos, usize)> =
             dijkstra_all(&location, |&p| self.successors_costed(p));
 
         let targets = reachable
@@ -279,18 +289,20 @@ mod game {
         #[test_case((1,7) => Some((1,6)))]
         #[test_case((4,1) => Some((4,2)))]
         fn example(location: (usize, usize)) -> Option<(usize, usize)> {
-            let game = generate(include_str!("day15_example_move.txt"));
+            let game = generate(include_str!("day15_example_move.txt")).unwrap();
             game.move_for(location)
         }
     }
 }
 
-impl Game {
-    fn step(&mut self, elfbuff: u8) {
+impl Simulation for Game {
+    type Key = Grid<Cell>;
+
+    fn step(&mut self) {
         let mut units = self
             .map
-            .indices()
-            .filter(|&p| matches!(self.map.get(p), Some(Cell::Mob(_))))
+            .positions()
+            .filter(|&p| matches!(self.map[p], Cell::Mob(_)))
             .collect_vec();
 
         units.reverse();
@@ -301,8 +313,8 @@ impl Game {
             }
 
             if let Some(step) = self.move_for(location) {
-                *self.map.get_mut(step).unwrap() = *self.map.get(location).unwrap();
-                *self.map.get_mut(location).unwrap() = Cell::Empty;
+                self.map[step] = self.map[location];
+                self.map[location] = Cell::Empty;
                 location = step;
             }
 
@@ -325,21 +337,33 @@ impl Game {
                 .unwrap();
             let target = self.unit_at(location);
             let damage = if mob.force == Force::Elf {
-                3 + elfbuff
+                3 + self.elfbuff
             } else {
                 3
             };
             let after = target.take_damage(damage);
             if after.health == 0 {
-                *self.map.get_mut(location).unwrap() = Cell::Empty;
+                self.map[location] = Cell::Empty;
                 units.retain(|&p| location != p);
             } else {
-                *self.map.get_mut(location).unwrap() = Cell::Mob(after);
+                self.map[location] = Cell::Mob(after);
             }
         }
 
         self.round += 1;
     }
+
+    fn is_finished(&self) -> bool {
+        self.is_over()
+    }
+
+    fn state_key(&self) -> Grid<Cell> {
+        self.map.clone()
+    }
+
+    fn render(&self) -> String {
+        self.to_string()
+    }
 }
 
 #[cfg(test)]
@@ -348,30 +372,29 @@ mod game_step {
 
     #[test]
     fn move_example() {
-        let mut game = generate(include_str!("day15_example_move.txt"));
-        game.step(0);
+        let mut game = generate(include_str!("day15_example_move.txt")).unwrap();
+        game.step();
         game.soft_reset();
-        assert_eq!(game, generate(include_str!("day15_example_move_2.txt")))
+        assert_eq!(
+            game,
+            generate(include_str!("day15_example_move_2.txt")).unwrap()
+        )
     }
 }
 
 #[aoc_generator(day15)]
-fn generate(input: &str) -> Game {
-    input.parse().unwrap()
+fn generate(input: &str) -> anyhow::Result<Game> {
+    input.parse()
 }
 
 #[aoc(day15, part1)]
 fn solve(game: &Game) -> usize {
-    // println!("{}", game);
     score(game)
 }
 
 fn score(game: &Game) -> usize {
     let mut game = (*game).clone();
-    while !game.is_over() {
-        game.step(0);
-        // println!("{}", game);
-    }
+    run_to_end(&mut game);
     game.score()
 }
 
@@ -381,32 +404,69 @@ mod score {
 
     #[test]
     fn example1() {
-        assert_eq!(score(&generate(include_str!("day15_example1.txt"))), 27730);
+        assert_eq!(
+            score(&generate(include_str!("day15_example1.txt")).unwrap()),
+            27730
+        );
     }
 
     #[test]
     fn example2() {
-        assert_eq!(score(&generate(include_str!("day15_example2.txt"))), 36334);
+        assert_eq!(
+            score(&generate(include_str!("day15_example2.txt")).unwrap()),
+            36334
+        );
     }
 
     #[test]
     fn example3() {
-        assert_eq!(score(&generate(include_str!("day15_example3.txt"))), 39514);
+        assert_eq!(
+            score(&generate(include_str!("day15_example3.txt")).unwrap()),
+            39514
+        );
     }
 
     #[test]
     fn example4() {
-        assert_eq!(score(&generate(include_str!("day15_example4.txt"))), 27755);
+        assert_eq!(
+            score(&generate(include_str!("day15_example4.txt")).unwrap()),
+            27755
+        );
     }
 
     #[test]
     fn example5() {
-        assert_eq!(score(&generate(include_str!("day15_example5.txt"))), 28944);
+        assert_eq!(
+            score(&generate(include_str!("day15_example5.txt")).unwrap()),
+            28944
+        );
     }
 
     #[test]
     fn example6() {
-        assert_eq!(score(&generate(include_str!("day15_example6.txt"))), 18740);
+        assert_eq!(
+            score(&generate(include_str!("day15_example6.txt")).unwrap()),
+            18740
+        );
+    }
+
+    #[test]
+    fn final_frame() {
+        let mut game = generate(include_str!("day15_example1.txt")).unwrap();
+        let frames = crate::simulation::frames(&mut game, 100);
+        assert_eq!(frames.len(), 48);
+        assert_eq!(
+            frames.last().unwrap(),
+            "Round 47
+#######
+#G....#   G(200)
+#.G...#   G(131)
+#.#.#G#   G(59)
+#...#.#
+#....G#   G(200)
+#######
+"
+        );
     }
 }
 
@@ -417,15 +477,15 @@ fn solve2(game: &Game) -> usize {
 
 fn elves_no_losses(game: &Game) -> usize {
     let starting_elves = game.elves();
-    'buffing: for buff in 1..255 {
-        let mut game = (*game).clone();
-        while !game.is_over() {
-            game.step(buff);
-            if game.elves() != starting_elves {
-                continue 'buffing;
-            }
+    for elfbuff in 1..255 {
+        let mut game = Game {
+            elfbuff,
+            ..(*game).clone()
+        };
+        run_until(&mut game, |g| g.elves() != starting_elves);
+        if game.elves() == starting_elves {
+            return game.score();
         }
-        return game.score();
     }
     unreachable!();
 }
@@ -437,7 +497,7 @@ mod elves_no_losses {
     #[test]
     fn example1() {
         assert_eq!(
-            elves_no_losses(&generate(include_str!("day15_example1.txt"))),
+            elves_no_losses(&generate(include_str!("day15_example1.txt")).unwrap()),
             4988
         );
     }
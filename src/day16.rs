@@ -1,92 +1,79 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
+use anyhow::{anyhow, bail, Context};
 use itertools::Itertools;
-use strum::IntoEnumIterator;
-use strum_macros::{Display, EnumIter};
 
-type Number = usize;
+use crate::wasm::{self, Cpu, Dialect, Number, Op};
 
 type Instruction = [Number; 4];
 type Registers = [Number; 4];
 
 #[derive(Debug)]
 struct Check {
+    line: usize,
     before: Registers,
     after: Registers,
     instr: Instruction,
 }
 
-#[derive(Debug, Display, Clone, Copy, EnumIter, PartialEq)]
-enum Opcode {
-    Addr,
-    Addi,
-    Mulr,
-    Muli,
-    Banr,
-    Bani,
-    Borr,
-    Bori,
-    Setr,
-    Seti,
-    Gtir,
-    Gtri,
-    Gtrr,
-    Eqir,
-    Eqri,
-    Eqrr,
-}
-
-fn apply(opcode: Opcode, instr: &Instruction, r: &Registers) -> Registers {
+// None if the op would name a register that doesn't exist, which rules it out.
+fn apply(op: Op, instr: &Instruction, r: &Registers) -> Option<Registers> {
+    let (ra, rb) = op.reads();
+    let registers = [(ra, instr[1]), (rb, instr[2]), (true, instr[3])];
+    if registers.iter().any(|&(used, n)| used && n >= r.len()) {
+        return None;
+    }
     let mut copy = *r;
-    let (a, b, c) = (instr[1], instr[2], instr[3]);
-
-    use Opcode::*;
-    copy[c] = match opcode {
-        Addr => r[a] + r[b],
-        Addi => r[a] + b,
-        Mulr => r[a] * r[b],
-        Muli => r[a] * b,
-        Banr => r[a] & r[b],
-        Bani => r[a] & b,
-        Borr => r[a] | r[b],
-        Bori => r[a] | b,
-        Setr => r[a],
-        Seti => a,
-        Gtir => usize::from(a > r[b]),
-        Gtri => usize::from(r[a] > b),
-        Gtrr => usize::from(r[a] > r[b]),
-        Eqir => usize::from(a == r[b]),
-        Eqri => usize::from(r[a] == b),
-        Eqrr => usize::from(r[a] == r[b]),
-    };
-    copy
+    wasm::Instruction::new(op, instr[1], instr[2], instr[3]).apply(&mut copy);
+    Some(copy)
+}
+
+fn numbers<'a, const N: usize>(
+    values: impl Iterator<Item = &'a str>,
+) -> anyhow::Result<[Number; N]> {
+    let values = values
+        .map(|v| v.trim().parse::<Number>())
+        .collect::<Result<Vec<_>, _>>()?;
+    values
+        .try_into()
+        .map_err(|v: Vec<_>| anyhow!("expected {} numbers, found {}", N, v.len()))
+}
+
+fn registers(s: &str, label: &str) -> anyhow::Result<Registers> {
+    let list = s
+        .strip_prefix(label)
+        .map(str::trim)
+        .and_then(|s| s.strip_prefix('['))
+        .and_then(|s| s.strip_suffix(']'))
+        .with_context(|| format!("expected \"{} [a, b, c, d]\"", label))?;
+    numbers(list.split(','))
 }
 
 #[aoc_generator(day16, part1)]
-fn generate(input: &str) -> Vec<Check> {
+fn generate(input: &str) -> anyhow::Result<Vec<Check>> {
     let mut checks = vec![];
-    for rec in input.lines().collect_vec().chunks(4) {
+    for (index, rec) in input.lines().collect_vec().chunks(4).enumerate() {
         if !rec[0].starts_with("Before:") {
             break;
         }
-        let (_, before) = rec[0].split_once('[').unwrap();
-        let before = before[..before.len() - 1]
-            .split(", ")
-            .map(|v| v.parse::<Number>().unwrap());
-        let instruction = rec[1]
-            .split_ascii_whitespace()
-            .map(|v| v.parse::<Number>().unwrap());
-        let (_, after) = rec[2].split_once('[').unwrap();
-        let after = after[..after.len() - 1]
-            .split(", ")
-            .map(|v| v.parse::<Number>().unwrap());
+        let line = index * 4 + 1;
+        let field = |offset: usize| {
+            let raw = rec.get(offset).copied().unwrap_or_default();
+            let at = || crate::parse::at(16, line + offset, raw);
+            match offset {
+                0 => registers(raw, "Before:").with_context(at),
+                1 => numbers(raw.split_ascii_whitespace()).with_context(at),
+                _ => registers(raw, "After:").with_context(at),
+            }
+        };
         checks.push(Check {
-            before: before.collect_vec().try_into().unwrap(),
-            after: after.collect_vec().try_into().unwrap(),
-            instr: instruction.collect_vec().try_into().unwrap(),
+            line,
+            before: field(0)?,
+            instr: field(1)?,
+            after: field(2)?,
         })
     }
-    checks
+    Ok(checks)
 }
 
 #[aoc(day16, part1)]
@@ -94,8 +81,8 @@ fn solve(checks: &[Check]) -> usize {
     checks
         .iter()
         .filter(|&check| {
-            Opcode::iter()
-                .filter(|&opcode| check.after == apply(opcode, &check.instr, &check.before))
+            Op::puzzle()
+                .filter(|&opcode| Some(check.after) == apply(opcode, &check.instr, &check.before))
                 .count()
                 >= 3
         })
@@ -109,41 +96,398 @@ struct System {
 }
 
 #[aoc_generator(day16, part2)]
-fn generate2(input: &str) -> System {
-    let (_, rest) = input.split_once("\n\n\n\n").unwrap();
-
-    System {
-        checks: generate(input),
-        program: rest
-            .lines()
-            .map(|l| {
-                l.split_ascii_whitespace()
-                    .map(|v| v.parse::<Number>().unwrap())
-                    .collect_vec()
-                    .try_into()
-                    .unwrap()
-            })
-            .collect_vec(),
+fn generate2(input: &str) -> anyhow::Result<System> {
+    let (samples, rest) = input
+        .split_once("\n\n\n\n")
+        .context("day 16: expected samples and a program separated by blank lines")?;
+
+    Ok(System {
+        checks: generate(input)?,
+        program: crate::parse::lines_from(16, samples.lines().count() + 4, rest, |l| {
+            numbers(l.split_ascii_whitespace())
+        })?,
+    })
+}
+
+type Mapping = BTreeMap<Number, Op>;
+
+#[derive(Debug, PartialEq, Eq)]
+struct Contradiction {
+    numbers: Vec<Number>,
+    ops: Vec<Op>,
+    lines: Vec<usize>,
+}
+
+impl std::fmt::Display for Contradiction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "opcodes {} can only be [{}] between them (samples on lines {})",
+            self.numbers.iter().join(", "),
+            self.ops.iter().join(", "),
+            self.lines.iter().join(", ")
+        )
     }
 }
 
-#[aoc(day16, part2)]
-fn solve2(system: &System) -> Number {
-    let mut known: HashMap<usize, Opcode> = HashMap::new();
-    while known.len() != 16 {
-        for check in &system.checks {
-            let matching = Opcode::iter()
-                .filter(|op| !known.values().contains(op))
-                .filter(|op| check.after == apply(*op, &check.instr, &check.before))
+#[derive(Debug, PartialEq, Eq)]
+enum Decoding {
+    Unique(Mapping),
+    // The first two mappings found; there may be more.
+    Ambiguous(Mapping, Mapping),
+    Contradiction(Contradiction),
+}
+
+struct Constraints {
+    candidates: BTreeMap<Number, Vec<Op>>,
+    ruled_out: HashMap<(Number, Op), usize>,
+}
+
+impl Constraints {
+    fn new(checks: &[Check]) -> Self {
+        let mut candidates: BTreeMap<Number, Vec<Op>> = BTreeMap::new();
+        let mut ruled_out = HashMap::new();
+        for check in checks {
+            let number = check.instr[0];
+            let ops = candidates
+                .entry(number)
+                .or_insert_with(|| Op::puzzle().collect());
+            ops.retain(|&op| {
+                let consistent = Some(check.after) == apply(op, &check.instr, &check.before);
+                if !consistent {
+                    ruled_out.entry((number, op)).or_insert(check.line);
+                }
+                consistent
+            });
+        }
+        Self {
+            candidates,
+            ruled_out,
+        }
+    }
+
+    fn propagate(&self) -> BTreeMap<Number, Vec<Op>> {
+        let mut candidates = self.candidates.clone();
+        loop {
+            let settled = candidates
+                .iter()
+                .filter(|(_, ops)| ops.len() == 1)
+                .map(|(&number, ops)| (number, ops[0]))
                 .collect_vec();
-            if matching.len() == 1 {
-                known.insert(check.instr[0], matching[0]);
+            let mut changed = false;
+            for (number, op) in settled {
+                for (&other, ops) in candidates.iter_mut() {
+                    if other != number && ops.contains(&op) {
+                        ops.retain(|&o| o != op);
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                return candidates;
             }
         }
     }
 
-    system.program.iter().fold([0; 4], |acc, instr| {
-        let opcode = known.get(&instr[0]).unwrap();
-        apply(*opcode, instr, &acc)
-    })[0]
+    // Backtracks over the candidates, stopping once `limit` mappings are found.
+    fn mappings(&self, limit: usize) -> Vec<Mapping> {
+        fn extend(
+            numbers: &[(&Number, &Vec<Op>)],
+            mapping: &mut Mapping,
+            found: &mut Vec<Mapping>,
+            limit: usize,
+        ) {
+            let Some(((&number, ops), rest)) = numbers.split_first() else {
+                found.push(mapping.clone());
+                return;
+            };
+            for &op in ops.iter() {
+                if found.len() == limit {
+                    return;
+                }
+                if !mapping.values().contains(&op) {
+                    mapping.insert(number, op);
+                    extend(rest, mapping, found, limit);
+                    mapping.remove(&number);
+                }
+            }
+        }
+
+        let candidates = self.propagate();
+        let numbers = candidates
+            .iter()
+            .sorted_by_key(|(_, ops)| ops.len())
+            .collect_vec();
+        let mut found = vec![];
+        extend(&numbers, &mut Mapping::new(), &mut found, limit);
+        found
+    }
+
+    // Matches numbers to ops one at a time along augmenting paths. When one
+    // cannot be matched, the failed search has visited every op reachable
+    // from it by alternating paths, all of them matched, and the numbers they
+    // are matched to plus this one outnumber them.
+    fn contradiction(&self) -> Option<Contradiction> {
+        fn augment(
+            candidates: &BTreeMap<Number, Vec<Op>>,
+            number: Number,
+            owners: &mut HashMap<Op, Number>,
+            seen: &mut HashSet<Op>,
+        ) -> bool {
+            for &op in &candidates[&number] {
+                if seen.insert(op)
+                    && owners
+                        .get(&op)
+                        .copied()
+                        .is_none_or(|owner| augment(candidates, owner, owners, seen))
+                {
+                    owners.insert(op, number);
+                    return true;
+                }
+            }
+            false
+        }
+
+        let mut owners = HashMap::new();
+        let (unmatched, seen) = self.candidates.keys().find_map(|&number| {
+            let mut seen = HashSet::new();
+            (!augment(&self.candidates, number, &mut owners, &mut seen)).then_some((number, seen))
+        })?;
+        let numbers = seen
+            .iter()
+            .map(|op| owners[op])
+            .chain([unmatched])
+            .sorted()
+            .collect_vec();
+        let ops = Op::puzzle().filter(|op| seen.contains(op)).collect_vec();
+        let lines = numbers
+            .iter()
+            .cartesian_product(Op::puzzle().filter(|op| !seen.contains(op)))
+            .filter_map(|(&number, op)| self.ruled_out.get(&(number, op)).copied())
+            .sorted()
+            .dedup()
+            .collect();
+        Some(Contradiction {
+            numbers,
+            ops,
+            lines,
+        })
+    }
+}
+
+fn decode(checks: &[Check]) -> Decoding {
+    let constraints = Constraints::new(checks);
+    // Without a matching the backtracking would try every partial mapping.
+    if let Some(contradiction) = constraints.contradiction() {
+        return Decoding::Contradiction(contradiction);
+    }
+    let mut mappings = constraints.mappings(2).into_iter();
+    match (mappings.next(), mappings.next()) {
+        (Some(mapping), None) => Decoding::Unique(mapping),
+        (Some(first), Some(second)) => Decoding::Ambiguous(first, second),
+        (None, _) => unreachable!("a complete matching is a mapping"),
+    }
+}
+
+fn mnemonics(mapping: &Mapping, program: &[Instruction]) -> anyhow::Result<String> {
+    program
+        .iter()
+        .map(|instr| {
+            let op = mapping
+                .get(&instr[0])
+                .ok_or_else(|| anyhow!("opcode {} never appears in the samples", instr[0]))?;
+            Ok(format!("{} {} {} {}\n", op, instr[1], instr[2], instr[3]))
+        })
+        .collect()
+}
+
+#[aoc(day16, part2)]
+fn solve2(system: &System) -> anyhow::Result<Number> {
+    let known = match decode(&system.checks) {
+        Decoding::Unique(mapping) => mapping,
+        Decoding::Ambiguous(first, second) => {
+            let differs = first.iter().find_map(|(number, op)| {
+                let other = second.get(number).filter(|&other| other != op)?;
+                Some((number, op, other))
+            });
+            match differs {
+                Some((number, op, other)) => bail!(
+                    "several mappings fit the samples: opcode {} could be {} or {}",
+                    number,
+                    op,
+                    other
+                ),
+                None => bail!("several mappings fit the samples"),
+            }
+        }
+        Decoding::Contradiction(contradiction) => bail!("{}", contradiction),
+    };
+
+    let mut cpu = Cpu::parse(&mnemonics(&known, &system.program)?, 4, Dialect::Puzzle)?;
+    cpu.run();
+    cpu.healthy().context("running the test program")?;
+    Ok(cpu.registers[0])
+}
+
+#[cfg(test)]
+const EXAMPLE: &str = "Before: [3, 2, 1, 1]
+9 2 1 2
+After:  [3, 2, 2, 1]
+";
+
+#[cfg(test)]
+#[test]
+fn test_solve() {
+    assert_eq!(solve(&generate(EXAMPLE).unwrap()), 1);
+}
+
+#[cfg(test)]
+fn samples(mapping: &[(Number, Op)]) -> Vec<Check> {
+    let states = [[3, 2, 1, 1], [5, 9, 13, 7], [12, 6, 3, 10], [0, 8, 8, 1]];
+    let operands = [[2, 1, 2], [0, 3, 1], [3, 2, 0], [1, 1, 3]];
+    mapping
+        .iter()
+        .cartesian_product(states.iter().zip(operands.iter()))
+        .enumerate()
+        .map(|(i, (&(number, op), (&before, &[a, b, c])))| {
+            let instr = [number, a, b, c];
+            Check {
+                line: i * 4 + 1,
+                before,
+                after: apply(op, &instr, &before).unwrap(),
+                instr,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+#[test]
+fn test_decode_unique() {
+    let mapping = Op::puzzle()
+        .enumerate()
+        .map(|(n, op)| (15 - n, op))
+        .collect_vec();
+    assert_eq!(
+        decode(&samples(&mapping)),
+        Decoding::Unique(mapping.into_iter().collect())
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_decode_ambiguous() {
+    let Decoding::Ambiguous(first, second) = decode(&generate(EXAMPLE).unwrap()) else {
+        panic!("expected several mappings");
+    };
+    assert_eq!((first[&9], second[&9]), (Op::Addi, Op::Mulr));
+
+    // Thirteen opcodes fit any op but the eq* ones and three fit only those:
+    // 3! * 13! mappings, so this only finishes if decoding stops at two.
+    let checks = (0..16)
+        .map(|number| Check {
+            line: 1,
+            before: [0; 4],
+            after: [0, Number::from(number < 3), 0, 0],
+            instr: [number, 0, 0, 1],
+        })
+        .collect_vec();
+    assert!(matches!(decode(&checks), Decoding::Ambiguous(..)));
+}
+
+#[cfg(test)]
+#[test]
+fn test_missing_registers() {
+    assert_eq!(apply(Op::Addr, &[0, 4, 0, 0], &[1; 4]), None);
+    assert_eq!(apply(Op::Addi, &[0, 0, 4, 9], &[1; 4]), None);
+    assert_eq!(apply(Op::Seti, &[0, 4, 9, 0], &[1; 4]), Some([4, 1, 1, 1]));
+    let check = generate("Before: [3, 2, 1, 1]\n9 7 1 2\nAfter:  [3, 2, 7, 1]\n").unwrap();
+    assert_eq!(solve(&check), 0);
+}
+
+#[cfg(test)]
+#[test]
+fn test_decode_contradiction() {
+    let checks = samples(&[(1, Op::Seti), (2, Op::Seti), (3, Op::Addr)]);
+    let Decoding::Contradiction(contradiction) = decode(&checks) else {
+        panic!("expected a contradiction");
+    };
+    assert_eq!(contradiction.numbers, vec![1, 2]);
+    assert_eq!(contradiction.ops, vec![Op::Seti]);
+    assert!(contradiction
+        .lines
+        .iter()
+        .all(|&line| checks.iter().any(|c| c.line == line && c.instr[0] != 3)));
+}
+
+#[cfg(test)]
+#[test]
+fn test_decode_contradiction_everywhere() {
+    // Sixteen opcodes that all leave r0 at zero, which rules out only the eq*
+    // ops: the first fourteen already have only thirteen ops between them.
+    let checks = (0..16)
+        .map(|number| Check {
+            line: number * 4 + 1,
+            before: [0; 4],
+            after: [0; 4],
+            instr: [number, 0, 0, 0],
+        })
+        .collect_vec();
+    let Decoding::Contradiction(contradiction) = decode(&checks) else {
+        panic!("expected a contradiction");
+    };
+    assert_eq!(contradiction.numbers, (0..14).collect_vec());
+    assert_eq!(contradiction.ops.len(), 13);
+    assert!(!contradiction.ops.contains(&Op::Eqrr));
+    assert_eq!(contradiction.lines.len(), 14);
+}
+
+#[cfg(test)]
+#[test]
+fn test_solve2_reports_contradiction() {
+    let system = System {
+        checks: samples(&[(1, Op::Seti), (2, Op::Seti)]),
+        program: vec![[1, 7, 0, 0]],
+    };
+    let error = solve2(&system).unwrap_err().to_string();
+    assert!(error.starts_with("opcodes 1, 2 can only be [seti] between them"));
+}
+
+#[cfg(test)]
+#[test]
+fn test_solve2() {
+    let mapping = Op::puzzle()
+        .enumerate()
+        .map(|(n, op)| (15 - n, op))
+        .collect_vec();
+    let number = |op| mapping.iter().find(|&&(_, o)| o == op).unwrap().0;
+    let system = System {
+        checks: samples(&mapping),
+        program: vec![[number(Op::Seti), 7, 0, 0], [number(Op::Addi), 0, 5, 0]],
+    };
+    assert_eq!(solve2(&system).unwrap(), 12);
+
+    let system = System {
+        program: vec![
+            [number(Op::Seti), Number::MAX, 0, 0],
+            [number(Op::Addi), 0, 5, 0],
+        ],
+        ..system
+    };
+    assert_eq!(
+        format!("{:#}", solve2(&system).unwrap_err()),
+        "running the test program: arithmetic overflow in \"addi 0 5 0\" at pc 1"
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_mnemonics() {
+    let mapping = Mapping::from([(3, Op::Seti), (8, Op::Addi)]);
+    let text = mnemonics(&mapping, &[[3, 7, 0, 0], [8, 0, 5, 0]]).unwrap();
+    assert_eq!(text, "seti 7 0 0\naddi 0 5 0\n");
+    let cpu = Cpu::parse(&text, 4, Dialect::Puzzle).unwrap();
+    assert_eq!(cpu.registers.len(), 4);
+    assert_eq!(cpu.pc_reg, None);
+    assert!(mnemonics(&mapping, &[[4, 0, 0, 0]]).is_err());
 }
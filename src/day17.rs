@@ -1,7 +1,9 @@
+use anyhow::{bail, Context};
 use lazy_static::lazy_static;
-use ndarray::prelude::*;
 use regex::Regex;
 
+use crate::grid::{Grid, Pos};
+
 #[derive(Debug, Clone, Copy, Default, PartialEq)]
 enum Cell {
     #[default]
@@ -16,64 +18,83 @@ struct Well {
     min_row: usize,
     max_row: usize,
     offset: usize,
-    cells: Array2<Cell>,
+    cells: Grid<Cell>,
 }
 
-impl std::fmt::Display for Well {
+impl std::fmt::Display for Cell {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for _ in 0..500 - self.offset {
-            write!(f, " ")?;
+        match self {
+            Cell::Empty => write!(f, "."),
+            Cell::Clay => write!(f, "#"),
+            Cell::Water => write!(f, "~"),
+            Cell::Reached => write!(f, "|"),
         }
-        writeln!(f, "+")?;
-        for r in 0..self.cells.nrows() {
-            for c in 0..self.cells.ncols() {
-                match self.cells[[r, c]] {
-                    Cell::Empty => write!(f, ".")?,
-                    Cell::Clay => write!(f, "#")?,
-                    Cell::Water => write!(f, "~")?,
-                    Cell::Reached => write!(f, "|")?,
-                }
-            }
-            writeln!(f)?;
-        }
-        Ok(())
     }
 }
 
+impl std::fmt::Display for Well {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{:>width$}", '+', width = 500 - self.offset + 1)?;
+        write!(f, "{}", self.cells)
+    }
+}
+
+const LIMIT: usize = 10_000;
+
 impl std::str::FromStr for Well {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut clay = vec![];
-        for line in s.lines() {
+        let veins = crate::parse::lines_with(17, s, |line| -> anyhow::Result<_> {
             lazy_static! {
                 static ref RE: Regex = Regex::new(r"([xy])=(\d+), [xy]=(\d+)..(\d+)").unwrap();
             }
-            let caps = RE.captures(line).unwrap();
+            let caps = RE
+                .captures(line)
+                .context("expected \"x=N, y=A..B\" or \"y=N, x=A..B\"")?;
             let fixed_axis = caps.get(1).unwrap().as_str();
-            let fixed_value = caps.get(2).unwrap().as_str().parse()?;
-            let range_from = caps.get(3).unwrap().as_str().parse()?;
-            let range_to = caps.get(4).unwrap().as_str().parse()?;
+            let fixed_value = caps[2].parse()?;
+            let range_from = caps[3].parse()?;
+            let range_to = caps[4].parse()?;
+            if [fixed_value, range_from, range_to]
+                .iter()
+                .any(|&v| v > LIMIT)
+            {
+                bail!("coordinates above {} would not fit the scan", LIMIT);
+            }
 
-            let grains = (range_from..=range_to).into_iter().map(|ranged_value| {
+            let grains = (range_from..=range_to).map(|ranged_value| {
                 if fixed_axis == "y" {
                     (fixed_value, ranged_value)
                 } else {
                     (ranged_value, fixed_value)
                 }
             });
-            clay.extend(grains);
+            Ok(grains.collect::<Vec<(usize, usize)>>())
+        })?;
+        let clay = veins.concat();
+        if clay.is_empty() {
+            bail!("day 17: no clay");
         }
 
-        let offset = clay.iter().map(|g| g.1).min().unwrap() - 1;
+        let offset = clay
+            .iter()
+            .map(|g| g.1)
+            .min()
+            .unwrap()
+            .checked_sub(1)
+            .context("day 17: clay at x=0 leaves no room to overflow")?;
         let min_row = clay.iter().map(|g| g.0).min().unwrap();
         let max_row = clay.iter().map(|g| g.0).max().unwrap();
         let rows = max_row + 2;
         let cols = clay.iter().map(|g| g.1).max().unwrap() - offset + 2;
-        let mut cells = Array::default((rows, cols));
+        if !(offset..offset + cols).contains(&500) {
+            bail!("day 17: the spring at x=500 is outside the scan");
+        }
+        let mut cells = Grid::new(rows, cols);
 
         clay.iter()
-            .for_each(|g| cells[[g.0, g.1 - offset]] = Cell::Clay);
+            .for_each(|g| cells[(g.0, g.1 - offset)] = Cell::Clay);
 
         Ok(Well {
             min_row,
@@ -89,11 +110,11 @@ impl Well {
         self.down((0, 500 - self.offset));
     }
 
-    fn down(&mut self, mut drop: (usize, usize)) {
+    fn down(&mut self, mut drop: Pos) {
         use Cell::*;
         loop {
             match self.cells.get(drop) {
-                Some(Empty) => *self.cells.get_mut(drop).unwrap() = Reached,
+                Some(Empty) => self.cells[drop] = Reached,
                 Some(Water) | Some(Clay) => return self.flood((drop.0 - 1, drop.1)), // Back up and fill?,
                 Some(Reached) => {}
                 None => return,
@@ -102,7 +123,7 @@ impl Well {
         }
     }
 
-    fn flood(&mut self, drop: (usize, usize)) {
+    fn flood(&mut self, drop: Pos) {
         use Cell::*;
         // check left
         let mut left_col = drop.1;
@@ -124,7 +145,7 @@ impl Well {
         //check right
         let mut right_col = drop.1;
         let mut right_drop = false;
-        while right_col < self.cells.dim().1 {
+        while right_col < self.cells.width() {
             match self.cells.get((drop.0 + 1, right_col)) {
                 Some(Water) | Some(Clay) => {}
                 _ => {
@@ -138,7 +159,7 @@ impl Well {
             right_col += 1;
         }
 
-        let mut fill = self.cells.slice_mut(s![drop.0, left_col + 1..right_col]);
+        let fill = &mut self.cells.row_mut(drop.0)[left_col + 1..right_col];
         if !right_drop && !left_drop {
             // If we're between walls, record and walk up
             fill.fill(Water);
@@ -157,26 +178,36 @@ impl Well {
         }
     }
 
+    fn scanned(&self) -> impl Iterator<Item = &Cell> {
+        (self.min_row..=self.max_row).flat_map(|row| self.cells.row(row))
+    }
+
     fn reached(&self) -> usize {
-        self.cells
-            .slice(s![self.min_row..=self.max_row, ..])
-            .iter()
+        self.scanned()
             .filter(|&c| matches!(c, Cell::Reached | Cell::Water))
             .count()
     }
 
     fn filled(&self) -> usize {
-        self.cells
-            .slice(s![self.min_row..=self.max_row, ..])
-            .iter()
-            .filter(|&c| matches!(c, Cell::Water))
-            .count()
+        self.scanned().filter(|&c| matches!(c, Cell::Water)).count()
     }
 }
 
 #[aoc_generator(day17)]
-fn generate(input: &str) -> Well {
-    input.parse().unwrap()
+fn generate(input: &str) -> anyhow::Result<Well> {
+    input.parse()
+}
+
+#[cfg(test)]
+mod scan {
+    use super::*;
+
+    #[test_case("x=0, y=1..2" => "day 17: clay at x=0 leaves no room to overflow")]
+    #[test_case("x=600, y=1..2" => "day 17: the spring at x=500 is outside the scan")]
+    #[test_case("x=495, y=1..4294967296" => "day 17 line 1: \"x=495, y=1..4294967296\": coordinates above 10000 would not fit the scan")]
+    fn rejects(input: &str) -> String {
+        format!("{:#}", generate(input).unwrap_err())
+    }
 }
 
 #[aoc(day17, part1)]
@@ -200,6 +231,9 @@ mod solve {
 
     #[test]
     fn example() {
-        assert_eq!(solve(&generate(include_str!("day17_example.txt"))), 57);
+        assert_eq!(
+            solve(&generate(include_str!("day17_example.txt")).unwrap()),
+            57
+        );
     }
 }
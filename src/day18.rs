@@ -1,10 +1,11 @@
-use std::collections::HashMap;
-
 use anyhow::bail;
-use itertools::Itertools;
-use ndarray::prelude::*;
 
-#[derive(Default, Clone, Copy, PartialEq)]
+use crate::{
+    grid::{Grid, Pos},
+    simulation::{run_for, skip_cycles, Simulation},
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 enum Cell {
     #[default]
     Open,
@@ -37,74 +38,44 @@ impl std::fmt::Display for Cell {
     }
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq)]
 struct Wood {
-    cells: Array2<Cell>,
+    cells: Grid<Cell>,
 }
 
 impl std::str::FromStr for Wood {
     type Err = anyhow::Error;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        let rows = input.lines().collect_vec().len();
-        let cols = input.lines().next().unwrap().len();
-        let data = input
-            .chars()
-            .filter_map(|c| Cell::try_from(c).ok())
-            .collect();
-
-        let cells = Array::from_shape_vec((rows, cols), data)?;
-
-        Ok(Wood { cells })
+        Ok(Wood {
+            cells: Grid::parse(18, input, Cell::try_from)?,
+        })
     }
 }
 
 impl std::fmt::Display for Wood {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for row in self.cells.rows() {
-            for col in row {
-                write!(f, "{}", col)?;
-            }
-            writeln!(f)?;
-        }
-        Ok(())
+        write!(f, "{}", self.cells)
     }
 }
 
 impl Wood {
-    fn neighbours(&self, (row, col): (usize, usize)) -> Vec<Cell> {
-        [
-            (-1, -1),
-            (-1, 0),
-            (-1, 1),
-            (0, -1),
-            (0, 1),
-            (1, -1),
-            (1, 0),
-            (1, 1),
-        ]
-        .iter()
-        .filter_map(|(dr, dc)| {
-            let nrow = row as i32 + dr;
-            let ncol = col as i32 + dc;
-            if nrow < 0 || ncol < 0 {
-                None
-            } else {
-                self.cells.get((nrow as usize, ncol as usize))
-            }
-        })
-        .copied()
-        .collect()
+    fn neighbours(&self, p: Pos) -> Vec<Cell> {
+        self.cells.adjacent(p).map(|p| self.cells[p]).collect()
     }
+}
+
+impl Simulation for Wood {
+    type Key = Grid<Cell>;
 
-    fn tick(&mut self) {
+    fn step(&mut self) {
         use Cell::*;
         let mut next = self.cells.clone();
-        for ((row, col), cell) in next.indexed_iter_mut() {
-            let neighbours = self.neighbours((row, col));
+        for p in next.positions() {
+            let neighbours = self.neighbours(p);
             let trees = neighbours.iter().filter(|c| **c == Tree).count();
             let mills = neighbours.iter().filter(|c| **c == Mill).count();
-            *cell = match (*cell, trees, mills) {
+            next[p] = match (next[p], trees, mills) {
                 (Open, trees, _) if trees >= 3 => Tree,
                 (Tree, _, mills) if mills >= 3 => Mill,
                 (Mill, trees, mills) if trees >= 1 && mills >= 1 => Mill,
@@ -115,6 +86,16 @@ impl Wood {
         self.cells = next;
     }
 
+    fn state_key(&self) -> Grid<Cell> {
+        self.cells.clone()
+    }
+
+    fn render(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Wood {
     fn value(&self) -> usize {
         let wood = self.cells.iter().filter(|&c| *c == Cell::Tree).count();
         let mill = self.cells.iter().filter(|&c| *c == Cell::Mill).count();
@@ -123,51 +104,68 @@ impl Wood {
 }
 
 #[aoc_generator(day18)]
-fn generate(input: &str) -> Wood {
-    input.parse().unwrap()
+fn generate(input: &str) -> anyhow::Result<Wood> {
+    input.parse()
+}
+
+#[cfg(test)]
+mod round_trip {
+    use proptest::prelude::*;
+
+    use super::{Cell::*, *};
+
+    proptest! {
+        #[test]
+        fn wood(
+            rows in crate::parse::rows(prop::sample::select(&[Open, Tree, Mill][..]), 8),
+        ) {
+            crate::parse::round_trips([Wood { cells: Grid::from_rows(rows).unwrap() }]);
+        }
+    }
 }
 
 #[aoc(day18, part1)]
 fn solve(wood: &Wood) -> usize {
     let mut wood = (*wood).clone();
-    for _ in 0..10 {
-        wood.tick();
-    }
-    println!("{}", wood);
+    run_for(&mut wood, 10);
     wood.value()
 }
 
 #[cfg(test)]
 #[test]
 fn solve_example() {
-    assert_eq!(solve(&generate(include_str!("day18_example.txt"))), 1147);
+    assert_eq!(
+        solve(&generate(include_str!("day18_example.txt")).unwrap()),
+        1147
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_example_frames() {
+    let mut wood = generate(include_str!("day18_example.txt")).unwrap();
+    let frames = crate::simulation::frames(&mut wood, 10);
+    assert_eq!(frames.len(), 11);
+    assert_eq!(
+        frames[10],
+        "\
+.||##.....
+||###.....
+||##......
+|##.....##
+|##.....##
+|##....##|
+||##.####|
+||#####|||
+||||#|||||
+||||||||||
+"
+    );
 }
 
 #[aoc(day18, part2)]
 fn solve2(start: &Wood) -> usize {
     let mut wood = (*start).clone();
-    let mut seen: HashMap<String, usize> = HashMap::new();
-    let target = 1_000_000_000;
-    let mut generation = 0;
-    let mut skipped = false;
-    while generation < target {
-        wood.tick();
-        generation += 1;
-        if skipped {
-            continue;
-        }
-
-        let view = format!("{}", wood);
-        if let Some(&when) = seen.get(&view) {
-            let stride = generation - when;
-            // found our loop; we can advance time
-            while generation + stride < target {
-                generation += stride;
-            }
-            skipped = true;
-        }
-        seen.insert(view, generation);
-    }
-    println!("{}", wood);
+    skip_cycles(&mut wood, 1_000_000_000);
     wood.value()
 }
@@ -1,37 +1,55 @@
-use crate::wasm::Cpu;
+use anyhow::Context;
+
+use crate::wasm::{optimize::Optimized, Cpu, Dialect};
 
 #[aoc_generator(day19)]
-fn generate(input: &str) -> Cpu {
-    input.parse().unwrap()
+fn generate(input: &str) -> anyhow::Result<Cpu> {
+    Cpu::parse(input, 6, Dialect::Puzzle).context("day 19")
+}
+
+fn run(cpu: &Cpu, r0: usize) -> anyhow::Result<usize> {
+    let mut machine = Optimized::new(cpu.clone());
+    machine.cpu.registers[0] = r0;
+    machine.run();
+    machine
+        .cpu
+        .healthy()
+        .with_context(|| format!("running with r0 = {}", r0))?;
+    Ok(machine.cpu.registers[0])
 }
 
 #[aoc(day19, part1)]
-fn solve(cpu: &Cpu) -> usize {
-    let mut cpu = (*cpu).clone();
-    cpu.run();
-    cpu.registers[0]
+fn solve(cpu: &Cpu) -> anyhow::Result<usize> {
+    run(cpu, 0)
 }
 
 #[cfg(test)]
-#[test]
-fn test_solve() {
-    assert_eq!(solve(&generate(include_str!("day19_example.txt"))), 6)
+#[test_case(include_str!("day19_example.txt") => 6)]
+#[test_case(include_str!("wasm/divisor_sum.txt") => 252)]
+fn test_solve(input: &str) -> usize {
+    solve(&generate(input).unwrap()).unwrap()
 }
 
 #[aoc(day19, part2)]
-fn solve2(cpu: &Cpu) -> usize {
-    let mut cpu = (*cpu).clone();
-    cpu.registers[0] = 1;
-    while cpu.registers[cpu.pc_reg] != 1 {
-        cpu.apply();
-    }
-
-    let seed = *cpu.registers.iter().max().unwrap();
-    let mut total = 0;
-    for i in 1..=seed {
-        if seed % i == 0 {
-            total += i;
-        }
-    }
-    total
+fn solve2(cpu: &Cpu) -> anyhow::Result<usize> {
+    run(cpu, 1)
+}
+
+#[cfg(test)]
+#[test]
+fn test_solve2() {
+    assert_eq!(
+        solve2(&generate(include_str!("wasm/divisor_sum.txt")).unwrap()).unwrap(),
+        1026000
+    )
+}
+
+#[cfg(test)]
+#[test]
+fn test_fault() {
+    let cpu = generate("#ip 5\nseti 18446744073709551615 0 1\naddr 0 1 0").unwrap();
+    assert_eq!(
+        format!("{:#}", solve2(&cpu).unwrap_err()),
+        "running with r0 = 1: arithmetic overflow in \"addr 0 1 0\" at pc 1"
+    );
 }
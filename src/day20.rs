@@ -1,39 +1,45 @@
-use std::collections::HashMap;
+use anyhow::{bail, Context};
+
+use crate::grid::{self, Offset, Point, SparseGrid};
 
 #[derive(Default)]
 struct Walker {
-    x: i32,
-    y: i32,
+    at: Point,
     steps: usize,
-    visited: HashMap<(i32, i32), usize>,
+    visited: SparseGrid<usize>,
 }
 
 impl Walker {
-    fn step(&mut self, x_delta: i32, y_delta: i32) {
-        self.x += x_delta;
-        self.y += y_delta;
+    fn step(&mut self, offset: Offset) {
+        self.at = grid::offset(self.at, offset).expect("a regex can't walk 2^63 doors");
         self.steps += 1;
 
         self.visited
-            .entry((self.x, self.y))
+            .entry(self.at)
             .and_modify(|e| *e = std::cmp::min(*e, self.steps))
             .or_insert(self.steps);
     }
 
-    fn tour(&mut self, path: &str) {
+    fn tour(&mut self, path: &str) -> anyhow::Result<()> {
         let mut stack = vec![];
-        for char in path.chars() {
+        for (i, char) in path.chars().enumerate() {
+            let unbalanced = || format!("day 20 column {}: unbalanced {:?}", i + 1, char);
             match char {
-                'N' => self.step(0, 1),
-                'S' => self.step(0, -1),
-                'E' => self.step(1, 0),
-                'W' => self.step(-1, 0),
-                '(' => stack.push((self.x, self.y, self.steps)),
-                ')' => (self.x, self.y, _) = stack.pop().unwrap(),
-                '|' => (self.x, self.y, self.steps) = stack[stack.len() - 1],
-                _ => {}
+                'N' => self.step(grid::UP),
+                'S' => self.step(grid::DOWN),
+                'E' => self.step(grid::RIGHT),
+                'W' => self.step(grid::LEFT),
+                '(' => stack.push((self.at, self.steps)),
+                ')' => (self.at, _) = stack.pop().with_context(unbalanced)?,
+                '|' => (self.at, self.steps) = *stack.last().with_context(unbalanced)?,
+                '^' | '$' => {}
+                _ => bail!("day 20 column {}: unexpected {:?}", i + 1, char),
             }
         }
+        if !stack.is_empty() {
+            bail!("day 20: {} unclosed '('", stack.len());
+        }
+        Ok(())
     }
 
     fn furthest(&self) -> usize {
@@ -45,17 +51,20 @@ impl Walker {
     }
 }
 
-#[aoc(day20, part1)]
-fn solve(s: &str) -> usize {
+#[aoc_generator(day20)]
+fn generate(s: &str) -> anyhow::Result<Walker> {
     let mut walker = Walker::default();
-    walker.tour(s);
+    walker.tour(s.trim())?;
+    Ok(walker)
+}
+
+#[aoc(day20, part1)]
+fn solve(walker: &Walker) -> usize {
     walker.furthest()
 }
 
 #[aoc(day20, part2)]
-fn solve2(s: &str) -> usize {
-    let mut walker = Walker::default();
-    walker.tour(s);
+fn solve2(walker: &Walker) -> usize {
     walker.far_away(1_000)
 }
 
@@ -66,5 +75,5 @@ fn solve2(s: &str) -> usize {
 #[test_case("^ESSWWN(E|NNENN(EESS(WNSE|)SSS|WWWSSSSE(SW|NNNE)))$" => 23)]
 #[test_case("^WSSEESWWWNW(S|NENNEEEENN(ESSSSW(NWSW|SSEN)|WSWWN(E|WWS(E|SS))))$" => 31)]
 fn _solve(s: &str) -> usize {
-    solve(s)
+    solve(&generate(s).unwrap())
 }
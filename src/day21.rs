@@ -1,32 +1,124 @@
 use std::collections::HashSet;
 
-use crate::wasm::Cpu;
+use anyhow::{bail, Context};
+
+use crate::wasm::{
+    compile::Compiled,
+    dataflow::{halting_value, Operand},
+    optimize::Optimized,
+    symbolic::{fewest_cycles_to_halt, Bounds},
+    Cpu, Dialect, Number,
+};
 
 #[aoc_generator(day21)]
-fn generate(input: &str) -> Cpu {
-    input.parse().unwrap()
+fn generate(input: &str) -> anyhow::Result<Cpu> {
+    Cpu::parse(input, 6, Dialect::Puzzle).context("day 21")
+}
+
+fn halting_register(cpu: &Cpu) -> anyhow::Result<(Number, usize)> {
+    match halting_value(cpu) {
+        Some(condition) => match condition.compared {
+            Operand::Register(register) => Ok((condition.pc, register)),
+            Operand::Immediate(_) => bail!("r0 is compared with a constant"),
+        },
+        None => bail!("no comparison with r0 controls halting"),
+    }
+}
+
+// After a run to the halting comparison, the value r0 is compared with.
+fn reached(cpu: &Cpu, pc: Number, register: usize) -> anyhow::Result<usize> {
+    cpu.healthy()?;
+    if cpu.halted() {
+        bail!("the program halted before reaching pc {}", pc);
+    }
+    Ok(cpu.registers[register])
 }
 
 #[aoc(day21, part1)]
-fn solve(cpu: &Cpu) -> usize {
-    let mut cpu = (*cpu).clone();
-    let last_eqrr = cpu.program.len() - 3;
-    cpu.run_till_pc(last_eqrr);
-    cpu.registers[cpu.program[last_eqrr].a]
+fn solve(cpu: &Cpu) -> anyhow::Result<usize> {
+    let (pc, register) = halting_register(cpu)?;
+    let mut machine = Optimized::new(cpu.clone());
+    machine.run_till_pc(pc);
+    reached(&machine.cpu, pc, register)
 }
 
-#[aoc(day21, part2)]
-fn solve2(cpu: &Cpu) -> usize {
-    let mut cpu = (*cpu).clone();
-    let last_eqrr = cpu.program.len() - 3;
+#[aoc(day21, part1, symbolic)]
+fn solve_symbolic(cpu: &Cpu) -> Option<usize> {
+    fewest_cycles_to_halt(cpu, Bounds::default()).map(|(r0, _)| r0)
+}
+
+#[cfg(test)]
+#[test]
+fn test_solve() {
+    let cpu = generate(include_str!("wasm/hash.txt")).unwrap();
+    assert_eq!(solve(&cpu).unwrap(), 47893);
+    assert_eq!(solve_symbolic(&cpu), Some(47893));
+}
+
+fn last_unique(mut next: impl FnMut() -> anyhow::Result<usize>) -> anyhow::Result<usize> {
     let mut seen: HashSet<usize> = HashSet::new();
     let mut prev = 0;
     loop {
-        cpu.run_till_pc(last_eqrr);
-        let value = cpu.registers[cpu.program[last_eqrr].a];
+        let value = next()?;
         if !seen.insert(value) {
-            return prev;
+            return Ok(prev);
         }
         prev = value;
     }
 }
+
+#[aoc(day21, part2)]
+fn solve2(cpu: &Cpu) -> anyhow::Result<usize> {
+    let (pc, register) = halting_register(cpu)?;
+    let mut machine = Optimized::new(cpu.clone());
+    last_unique(|| {
+        machine.run_till_pc(pc);
+        reached(&machine.cpu, pc, register)
+    })
+}
+
+#[aoc(day21, part2, compiled)]
+fn solve2_compiled(cpu: &Cpu) -> anyhow::Result<usize> {
+    let (pc, register) = halting_register(cpu)?;
+    let compiled = Compiled::new(cpu, &[pc]);
+    let mut machine = cpu.clone();
+    last_unique(|| {
+        compiled.run_till_pc(&mut machine, pc);
+        reached(&machine, pc, register)
+    })
+}
+
+#[cfg(test)]
+#[test]
+fn test_solve2() {
+    let cpu = generate(include_str!("wasm/hash.txt")).unwrap();
+    assert_eq!(solve2(&cpu).unwrap(), 9733);
+    assert_eq!(solve2_compiled(&cpu).unwrap(), 9733);
+}
+
+#[cfg(test)]
+#[test_case("#ip 4\neqri 0 7 3\naddr 3 4 4\nseti 0 0 4" => "r0 is compared with a constant")]
+#[test_case("#ip 4\nseti 0 0 1\naddi 1 1 1\nseti 0 0 4" => "no comparison with r0 controls halting")]
+fn test_unexpected_shape(input: &str) -> String {
+    let cpu = generate(input).unwrap();
+    let errors =
+        [solve(&cpu), solve2(&cpu), solve2_compiled(&cpu)].map(|r| r.unwrap_err().to_string());
+    assert!(errors.iter().all(|e| *e == errors[0]));
+    errors[0].clone()
+}
+
+#[cfg(test)]
+#[test]
+fn test_fault() {
+    // Without the masks r1 keeps growing until the multiplication overflows.
+    let text =
+        include_str!("wasm/hash.txt").replace("bani 1 65535 1", "bani 1 18446744073709551615 1");
+    let cpu = generate(&text).unwrap();
+    assert!(solve(&cpu).is_ok());
+    for result in [solve2(&cpu), solve2_compiled(&cpu)] {
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "arithmetic overflow in \"muli 1 263 1\" at pc 6"
+        );
+    }
+}
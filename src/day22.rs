@@ -1,8 +1,9 @@
-use std::collections::HashMap;
-
+use anyhow::Context;
 use itertools::{iproduct, Itertools};
 use pathfinding::prelude::dijkstra;
 
+use crate::grid::{self, Point, SparseGrid};
+
 enum Terrain {
     Rocky,
     Wet,
@@ -16,11 +17,11 @@ enum Tool {
     Gear,
 }
 
-#[derive(Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq)]
 struct Cave {
     depth: usize,
-    target: (usize, usize),
-    geologic: HashMap<(usize, usize), usize>,
+    target: Point,
+    geologic: SparseGrid<usize>,
 }
 
 impl std::str::FromStr for Cave {
@@ -28,47 +29,61 @@ impl std::str::FromStr for Cave {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let lines = s.lines().collect_vec();
-        let depth = lines[0].strip_prefix("depth: ").unwrap().parse()?;
-        let (x, y) = lines[1]
-            .strip_prefix("target: ")
-            .unwrap()
-            .split_once(',')
-            .unwrap();
+        let line = |n: usize| lines.get(n - 1).copied().unwrap_or_default();
+        let depth = |l: &str| -> anyhow::Result<usize> {
+            Ok(l.strip_prefix("depth: ")
+                .context("expected \"depth: N\"")?
+                .parse()?)
+        };
+        let target = |l: &str| -> anyhow::Result<Point> {
+            let (x, y) = l
+                .strip_prefix("target: ")
+                .and_then(|t| t.split_once(','))
+                .context("expected \"target: X,Y\"")?;
+            Ok((y.parse::<u32>()?.into(), x.parse::<u32>()?.into()))
+        };
         Ok(Self {
-            depth,
-            target: (x.parse()?, y.parse()?),
+            depth: depth(line(1)).with_context(|| crate::parse::at(22, 1, line(1)))?,
+            target: target(line(2)).with_context(|| crate::parse::at(22, 2, line(2)))?,
             ..Default::default()
         })
     }
 }
 
+impl std::fmt::Display for Cave {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "depth: {}", self.depth)?;
+        writeln!(f, "target: {},{}", self.target.1, self.target.0)
+    }
+}
+
 impl Cave {
-    fn geologic(&mut self, x: usize, y: usize) -> usize {
-        if let Some(value) = self.geologic.get(&(x, y)) {
-            return *value;
+    fn geologic(&mut self, p: Point) -> usize {
+        if let Some(&value) = self.geologic.get(p) {
+            return value;
         }
-        let value = match (x, y) {
+        let value = match p {
             (0, 0) => 0,
-            (x, y) if (x, y) == self.target => 0,
-            (x, 0) => x * 16807,
-            (0, y) => y * 48271,
-            (x, y) => self.erosion(x - 1, y) * self.erosion(x, y - 1),
+            p if p == self.target => 0,
+            (0, x) => x as usize * 16807,
+            (y, 0) => y as usize * 48271,
+            (y, x) => self.erosion((y, x - 1)) * self.erosion((y - 1, x)),
         };
-        self.geologic.insert((x, y), value);
+        self.geologic.insert(p, value);
         value
     }
 
-    fn erosion(&mut self, x: usize, y: usize) -> usize {
-        (self.geologic(x, y) + self.depth) % 20183
+    fn erosion(&mut self, p: Point) -> usize {
+        (self.geologic(p) + self.depth) % 20183
     }
 
-    fn region(&mut self, x: usize, y: usize) -> usize {
-        self.erosion(x, y) % 3
+    fn region(&mut self, p: Point) -> usize {
+        self.erosion(p) % 3
     }
 
-    fn terrain(&mut self, x: usize, y: usize) -> Terrain {
+    fn terrain(&mut self, p: Point) -> Terrain {
         use Terrain::*;
-        match self.region(x, y) {
+        match self.region(p) {
             0 => Rocky,
             1 => Wet,
             2 => Narrow,
@@ -78,7 +93,7 @@ impl Cave {
 
     fn risk_level(&mut self) -> usize {
         iproduct!(0..=self.target.0, 0..=self.target.1)
-            .map(|(x, y)| self.region(x, y))
+            .map(|p| self.region(p))
             .sum()
     }
 
@@ -96,43 +111,36 @@ impl Cave {
         )
     }
 
-    fn neighbours(&mut self, x: usize, y: usize, tool: Tool) -> Vec<((usize, usize, Tool), usize)> {
-        let terrain = self.terrain(x, y);
+    fn neighbours(&mut self, p: Point, tool: Tool) -> Vec<((Point, Tool), usize)> {
+        let terrain = self.terrain(p);
         let mut neighbours = vec![];
         use Terrain::*;
         use Tool::*;
         // swap tool
         match (terrain, tool) {
-            (Rocky, Torch) => neighbours.push(((x, y, Gear), 7)),
-            (Rocky, Gear) => neighbours.push(((x, y, Torch), 7)),
-            (Wet, None) => neighbours.push(((x, y, Gear), 7)),
-            (Wet, Gear) => neighbours.push(((x, y, None), 7)),
-            (Narrow, None) => neighbours.push(((x, y, Torch), 7)),
-            (Narrow, Torch) => neighbours.push(((x, y, None), 7)),
+            (Rocky, Torch) => neighbours.push(((p, Gear), 7)),
+            (Rocky, Gear) => neighbours.push(((p, Torch), 7)),
+            (Wet, None) => neighbours.push(((p, Gear), 7)),
+            (Wet, Gear) => neighbours.push(((p, None), 7)),
+            (Narrow, None) => neighbours.push(((p, Torch), 7)),
+            (Narrow, Torch) => neighbours.push(((p, None), 7)),
             (_, _) => (),
         }
 
-        // given current tool
-        if x > 0 && Self::legal_tool(self.terrain(x - 1, y), tool) {
-            neighbours.push(((x - 1, y, tool), 1))
-        }
-        if Self::legal_tool(self.terrain(x + 1, y), tool) {
-            neighbours.push(((x + 1, y, tool), 1))
-        }
-        if y > 0 && Self::legal_tool(self.terrain(x, y - 1), tool) {
-            neighbours.push(((x, y - 1, tool), 1))
-        }
-        if Self::legal_tool(self.terrain(x, y + 1), tool) {
-            neighbours.push(((x, y + 1, tool), 1))
+        // given current tool, never into the rock left of or above the mouth
+        for next in grid::neighbours(p).filter(|&(y, x)| y >= 0 && x >= 0) {
+            if Self::legal_tool(self.terrain(next), tool) {
+                neighbours.push(((next, tool), 1))
+            }
         }
         neighbours
     }
 
     fn rescue_time(&mut self) -> usize {
-        let target = (self.target.0, self.target.1, Tool::Torch);
+        let target = (self.target, Tool::Torch);
         let (_path, cost) = dijkstra(
-            &(0, 0, Tool::Torch),
-            |&(x, y, tool)| self.neighbours(x, y, tool),
+            &((0, 0), Tool::Torch),
+            |&(p, tool)| self.neighbours(p, tool),
             |&node| target == node,
         )
         .unwrap();
@@ -141,8 +149,26 @@ impl Cave {
 }
 
 #[aoc_generator(day22)]
-fn generate(input: &str) -> Cave {
-    input.parse().unwrap()
+fn generate(input: &str) -> anyhow::Result<Cave> {
+    input.parse()
+}
+
+#[cfg(test)]
+mod round_trip {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn cave(depth: usize, x: u32, y: u32) {
+            crate::parse::round_trips([Cave {
+                depth,
+                target: (y.into(), x.into()),
+                ..Default::default()
+            }]);
+        }
+    }
 }
 
 #[aoc(day22, part1)]
@@ -153,7 +179,10 @@ fn solve(cave: &Cave) -> usize {
 #[cfg(test)]
 #[test]
 fn test_solve() {
-    assert_eq!(solve(&generate(include_str!("day22_example.txt"))), 114)
+    assert_eq!(
+        solve(&generate(include_str!("day22_example.txt")).unwrap()),
+        114
+    )
 }
 
 #[aoc(day22, part2)]
@@ -164,5 +193,8 @@ fn solve2(cave: &Cave) -> usize {
 #[cfg(test)]
 #[test]
 fn test_solve2() {
-    assert_eq!(solve2(&generate(include_str!("day22_example.txt"))), 45)
+    assert_eq!(
+        solve2(&generate(include_str!("day22_example.txt")).unwrap()),
+        45
+    )
 }
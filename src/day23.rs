@@ -1,5 +1,6 @@
 use std::cmp::Ordering;
 
+use anyhow::Context;
 use itertools::{iproduct, Itertools, MinMaxResult};
 
 #[derive(Debug, Default, Clone, Copy, Hash, PartialEq, Eq)]
@@ -13,13 +14,12 @@ impl std::str::FromStr for Point3 {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let [x, y, z]: [&str; 3] = s[..s.len() - 1]
+        let [x, y, z]: [&str; 3] = s
             .strip_prefix("pos=<")
-            .unwrap()
-            .split(',')
-            .collect_vec()
-            .try_into()
-            .unwrap();
+            .and_then(|s| s.strip_suffix('>'))
+            .map(|s| s.split(',').collect_vec())
+            .and_then(|v| v.try_into().ok())
+            .context("expected \"pos=<x,y,z>\"")?;
         Ok(Self {
             x: x.parse()?,
             y: y.parse()?,
@@ -28,6 +28,12 @@ impl std::str::FromStr for Point3 {
     }
 }
 
+impl std::fmt::Display for Point3 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "pos=<{},{},{}>", self.x, self.y, self.z)
+    }
+}
+
 impl Point3 {
     fn new(x: i32, y: i32, z: i32) -> Self {
         Self { x, y, z }
@@ -76,7 +82,9 @@ impl std::str::FromStr for Nanobot {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (point, radius) = s.split_once(", r=").unwrap();
+        let (point, radius) = s
+            .split_once(", r=")
+            .context("expected \"pos=<x,y,z>, r=N\"")?;
         Ok(Self {
             position: point.parse()?,
             radius: radius.parse()?,
@@ -84,7 +92,13 @@ impl std::str::FromStr for Nanobot {
     }
 }
 
-#[derive(Default)]
+impl std::fmt::Display for Nanobot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}, r={}", self.position, self.radius)
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
 struct Swarm {
     bots: Vec<Nanobot>,
 }
@@ -94,11 +108,20 @@ impl std::str::FromStr for Swarm {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         Ok(Self {
-            bots: s.lines().map(|l| l.parse().unwrap()).collect_vec(),
+            bots: crate::parse::lines(23, s)?,
         })
     }
 }
 
+impl std::fmt::Display for Swarm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for bot in &self.bots {
+            writeln!(f, "{}", bot)?;
+        }
+        Ok(())
+    }
+}
+
 impl Swarm {
     fn connected(&self, bot: &Nanobot) -> usize {
         self.bots
@@ -113,9 +136,18 @@ impl Swarm {
     }
 
     fn closest_cluster(&self) -> u32 {
-        let MinMaxResult::MinMax(minx, maxx) = self.bots.iter().map(|b| b.position.x).minmax() else { unreachable!() };
-        let MinMaxResult::MinMax(miny, maxy) = self.bots.iter().map(|b| b.position.y).minmax() else { unreachable!() };
-        let MinMaxResult::MinMax(minz, maxz) = self.bots.iter().map(|b| b.position.z).minmax() else { unreachable!() };
+        let MinMaxResult::MinMax(minx, maxx) = self.bots.iter().map(|b| b.position.x).minmax()
+        else {
+            unreachable!()
+        };
+        let MinMaxResult::MinMax(miny, maxy) = self.bots.iter().map(|b| b.position.y).minmax()
+        else {
+            unreachable!()
+        };
+        let MinMaxResult::MinMax(minz, maxz) = self.bots.iter().map(|b| b.position.z).minmax()
+        else {
+            unreachable!()
+        };
 
         let mut min = Point3::new(minx, miny, minz);
         let mut max = Point3::new(maxx, maxy, maxz);
@@ -157,8 +189,31 @@ impl Swarm {
 }
 
 #[aoc_generator(day23)]
-fn generate(input: &str) -> Swarm {
-    input.parse().unwrap()
+fn generate(input: &str) -> anyhow::Result<Swarm> {
+    input.parse()
+}
+
+#[cfg(test)]
+mod round_trip {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn swarm(
+            bots in prop::collection::vec(any::<(i32, i32, i32, u32)>(), 0..20),
+        ) {
+            let bots = bots
+                .into_iter()
+                .map(|(x, y, z, radius)| Nanobot {
+                    position: Point3::new(x, y, z),
+                    radius,
+                })
+                .collect();
+            crate::parse::round_trips([Swarm { bots }]);
+        }
+    }
 }
 
 #[aoc(day23, part1)]
@@ -169,7 +224,10 @@ fn solve(swarm: &Swarm) -> usize {
 #[cfg(test)]
 #[test]
 fn test_solve() {
-    assert_eq!(solve(&generate(include_str!("day23_example.txt"))), 7)
+    assert_eq!(
+        solve(&generate(include_str!("day23_example.txt")).unwrap()),
+        7
+    )
 }
 
 #[aoc(day23, part2)]
@@ -180,5 +238,8 @@ fn solve2(swarm: &Swarm) -> u32 {
 #[cfg(test)]
 #[test]
 fn test_solve2() {
-    assert_eq!(solve2(&generate(include_str!("day23_example2.txt"))), 36)
+    assert_eq!(
+        solve2(&generate(include_str!("day23_example2.txt")).unwrap()),
+        36
+    )
 }
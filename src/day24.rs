@@ -1,9 +1,12 @@
 use std::{cmp::Ordering, collections::HashSet};
 
+use anyhow::Context;
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::Regex;
 
+use crate::simulation::Simulation;
+
 #[derive(Default, Debug, PartialEq, Clone, Copy)]
 enum Force {
     #[default]
@@ -23,6 +26,15 @@ impl std::str::FromStr for Force {
     }
 }
 
+impl std::fmt::Display for Force {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Force::Immune => write!(f, "Immune System:"),
+            Force::Infect => write!(f, "Infection:"),
+        }
+    }
+}
+
 #[derive(Default, Debug, PartialEq, Clone, Copy)]
 enum Damage {
     #[default]
@@ -48,6 +60,18 @@ impl std::str::FromStr for Damage {
     }
 }
 
+impl std::fmt::Display for Damage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Damage::Bludgeoning => write!(f, "bludgeoning"),
+            Damage::Cold => write!(f, "cold"),
+            Damage::Fire => write!(f, "fire"),
+            Damage::Radiation => write!(f, "radiation"),
+            Damage::Slashing => write!(f, "slashing"),
+        }
+    }
+}
+
 #[derive(Debug, Default, PartialEq, Clone)]
 struct Squad {
     force: Force,
@@ -80,25 +104,43 @@ impl std::str::FromStr for Squad {
             ..Default::default()
         };
         if let Some(modifiers) = caps.get(4) {
-            modifiers.as_str().split("; ").for_each(|chunk| {
+            for chunk in modifiers.as_str().split("; ") {
                 if let Some(effects) = chunk.strip_prefix("weak to ") {
-                    squad.weak = effects
-                        .split(", ")
-                        .map(|d| d.parse().unwrap())
-                        .collect_vec()
+                    squad.weak = effects.split(", ").map(str::parse).try_collect()?;
                 }
                 if let Some(effects) = chunk.strip_prefix("immune to ") {
-                    squad.immune = effects
-                        .split(", ")
-                        .map(|d| d.parse().unwrap())
-                        .collect_vec()
+                    squad.immune = effects.split(", ").map(str::parse).try_collect()?;
                 }
-            });
+            }
         }
         Ok(squad)
     }
 }
 
+// The force is not part of the line; Battlefield prints it as a section header.
+impl std::fmt::Display for Squad {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} units each with {} hit points",
+            self.units, self.hitpoints
+        )?;
+        let modifiers = [("weak", &self.weak), ("immune", &self.immune)]
+            .into_iter()
+            .filter(|(_, effects)| !effects.is_empty())
+            .map(|(kind, effects)| format!("{} to {}", kind, effects.iter().join(", ")))
+            .collect_vec();
+        if !modifiers.is_empty() {
+            write!(f, " ({})", modifiers.join("; "))?;
+        }
+        write!(
+            f,
+            " with an attack that does {} {} damage at initiative {}",
+            self.attack, self.damage, self.initiative
+        )
+    }
+}
+
 #[cfg(test)]
 mod squad_fromstr {
     use super::*;
@@ -184,7 +226,7 @@ impl Squad {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 struct Battlefield {
     squads: Vec<Squad>,
 }
@@ -195,14 +237,15 @@ impl std::str::FromStr for Battlefield {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut squads = vec![];
         let mut force: Force = Force::default();
-        for line in s.lines() {
+        for (i, line) in s.lines().enumerate() {
             if line.is_empty() {
                 continue;
             }
+            let at = || crate::parse::at(24, i + 1, line);
             if line.starts_with('I') {
-                force = line.parse().unwrap();
+                force = line.parse().with_context(at)?;
             } else {
-                let mut squad: Squad = line.parse().unwrap();
+                let mut squad: Squad = line.parse().with_context(at)?;
                 squad.force = force;
                 squads.push(squad);
             }
@@ -212,6 +255,23 @@ impl std::str::FromStr for Battlefield {
     }
 }
 
+impl std::fmt::Display for Battlefield {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut force = None;
+        for squad in &self.squads {
+            if force != Some(squad.force) {
+                if force.is_some() {
+                    writeln!(f)?;
+                }
+                writeln!(f, "{}", squad.force)?;
+                force = Some(squad.force);
+            }
+            writeln!(f, "{}", squad)?;
+        }
+        Ok(())
+    }
+}
+
 impl Battlefield {
     fn targets(&self) -> Vec<(usize, usize)> {
         let mut targets = vec![];
@@ -272,13 +332,16 @@ mod battlefield_targets {
 
     #[test]
     fn example_1() {
-        let battlefield = generate(include_str!("day24_example.txt"));
+        let battlefield = generate(include_str!("day24_example.txt")).unwrap();
         assert_eq!(battlefield.targets(), vec![(3, 1), (1, 2), (0, 3), (2, 0)])
     }
 }
 
-impl Battlefield {
-    fn damage_round(&mut self) {
+impl Simulation for Battlefield {
+    // Squads only ever lose units, so a repeat means nobody can hurt anyone.
+    type Key = Vec<usize>;
+
+    fn step(&mut self) {
         for (attacker, defender) in self.targets() {
             if self.squads[attacker].dead() {
                 continue;
@@ -292,6 +355,20 @@ impl Battlefield {
         }
     }
 
+    fn is_finished(&self) -> bool {
+        self.winning_score().is_some()
+    }
+
+    fn state_key(&self) -> Vec<usize> {
+        self.squads.iter().map(|s| s.units).collect()
+    }
+
+    fn render(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Battlefield {
     fn immune_score(&self) -> usize {
         self.squads
             .iter()
@@ -316,23 +393,19 @@ impl Battlefield {
         }
     }
 
+    // Units only ever go down, so a stalemate shows up as a round that
+    // changes nothing; there is no need to remember older states.
     fn play_to_win(&mut self) -> Option<usize> {
-        let mut last_immune = self.immune_score();
-        let mut last_infect = self.infect_score();
-        loop {
-            self.damage_round();
-
-            let immune = self.immune_score();
-            let infect = self.infect_score();
-            if last_immune == immune && last_infect == infect {
+        let mut previous = self.state_key();
+        while !self.is_finished() {
+            self.step();
+            let key = self.state_key();
+            if key == previous {
                 return None;
             }
-            last_immune = immune;
-            last_infect = infect;
-            if let Some(score) = self.winning_score() {
-                return Some(score);
-            }
+            previous = key;
         }
+        self.winning_score()
     }
 
     fn boost(&mut self, boost: usize) {
@@ -345,8 +418,44 @@ impl Battlefield {
 }
 
 #[aoc_generator(day24)]
-fn generate(s: &str) -> Battlefield {
-    s.parse().unwrap()
+fn generate(s: &str) -> anyhow::Result<Battlefield> {
+    s.parse()
+}
+
+#[cfg(test)]
+mod round_trip {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn battlefield(
+            squads in prop::collection::vec(
+                (
+                    prop::sample::select(&[Force::Immune, Force::Infect][..]),
+                    any::<[usize; 4]>(),
+                    prop::collection::vec(damage(), 0..3),
+                    prop::collection::vec(damage(), 0..3),
+                    damage(),
+                ),
+                0..10,
+            ),
+        ) {
+            let squads = squads
+                .into_iter()
+                .map(|(force, [units, hitpoints, attack, initiative], weak, immune, damage)| {
+                    Squad { force, units, hitpoints, weak, immune, attack, damage, initiative }
+                })
+                .collect();
+            crate::parse::round_trips([Battlefield { squads }]);
+        }
+    }
+
+    fn damage() -> impl Strategy<Value = Damage> {
+        use Damage::*;
+        prop::sample::select(&[Bludgeoning, Cold, Fire, Radiation, Slashing][..])
+    }
 }
 
 #[aoc(day24, part1)]
@@ -358,17 +467,34 @@ fn solve(battlefield: &Battlefield) -> usize {
 #[cfg(test)]
 #[test]
 fn test_solve() {
-    assert_eq!(solve(&generate(include_str!("day24_example.txt"))), 5216)
+    assert_eq!(
+        solve(&generate(include_str!("day24_example.txt")).unwrap()),
+        5216
+    )
 }
 
 #[cfg(test)]
 #[test]
 fn test_boosted() {
-    let mut battlefield = generate(include_str!("day24_example.txt"));
+    let mut battlefield = generate(include_str!("day24_example.txt")).unwrap();
     battlefield.boost(1570);
     assert_eq!(battlefield.play_to_win().unwrap(), 51);
 }
 
+#[cfg(test)]
+#[test]
+fn test_stalemate() {
+    let mut battlefield = generate(
+        "Immune System:
+10 units each with 10 hit points (immune to fire) with an attack that does 5 cold damage at initiative 1
+
+Infection:
+10 units each with 10 hit points (immune to cold) with an attack that does 5 fire damage at initiative 2",
+    )
+    .unwrap();
+    assert_eq!(battlefield.play_to_win(), None);
+}
+
 #[aoc(day24, part2)]
 fn solve2(battlefield: &Battlefield) -> usize {
     for boost in 1.. {
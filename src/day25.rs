@@ -1,21 +1,23 @@
 use itertools::Itertools;
 use pathfinding::prelude::connected_components;
 
-#[derive(Hash, Clone, PartialEq, Eq)]
+#[derive(Debug, Hash, Clone, PartialEq, Eq)]
 struct Point([i32; 4]);
 
 impl std::str::FromStr for Point {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Self(
-            s.trim()
-                .split(',')
-                .map(|v| v.parse().unwrap())
-                .collect_vec()
-                .try_into()
-                .unwrap(),
-        ))
+        let values: Vec<i32> = s.trim().split(',').map(str::parse).try_collect()?;
+        Ok(Self(values.try_into().map_err(|v: Vec<_>| {
+            anyhow::anyhow!("expected 4 coordinates, found {}", v.len())
+        })?))
+    }
+}
+
+impl std::fmt::Display for Point {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.iter().join(","))
     }
 }
 
@@ -30,8 +32,22 @@ impl Point {
 }
 
 #[aoc_generator(day25)]
-fn generate(s: &str) -> Vec<Point> {
-    s.lines().map(|l| l.parse().unwrap()).collect()
+fn generate(s: &str) -> anyhow::Result<Vec<Point>> {
+    crate::parse::lines(25, s)
+}
+
+#[cfg(test)]
+mod round_trip {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn point(coordinates: [i32; 4]) {
+            crate::parse::round_trips([Point(coordinates)]);
+        }
+    }
 }
 
 #[aoc(day25, part1)]
@@ -52,21 +68,33 @@ mod solve {
 
     #[test]
     fn example_1() {
-        assert_eq!(solve(&generate(include_str!("day25_example_1.txt"))), 2);
+        assert_eq!(
+            solve(&generate(include_str!("day25_example_1.txt")).unwrap()),
+            2
+        );
     }
 
     #[test]
     fn example_2() {
-        assert_eq!(solve(&generate(include_str!("day25_example_2.txt"))), 4);
+        assert_eq!(
+            solve(&generate(include_str!("day25_example_2.txt")).unwrap()),
+            4
+        );
     }
 
     #[test]
     fn example_3() {
-        assert_eq!(solve(&generate(include_str!("day25_example_3.txt"))), 3);
+        assert_eq!(
+            solve(&generate(include_str!("day25_example_3.txt")).unwrap()),
+            3
+        );
     }
 
     #[test]
     fn example_4() {
-        assert_eq!(solve(&generate(include_str!("day25_example_4.txt"))), 8);
+        assert_eq!(
+            solve(&generate(include_str!("day25_example_4.txt")).unwrap()),
+            8
+        );
     }
 }
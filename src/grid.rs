@@ -0,0 +1,420 @@
+use std::collections::HashMap;
+
+use anyhow::bail;
+use itertools::{iproduct, Itertools, MinMaxResult};
+
+// Positions are (row, col), rows growing downwards, so tuple order is reading order.
+pub type Pos = (usize, usize);
+pub type Point = (i64, i64);
+pub type Offset = (i64, i64);
+
+pub const UP: Offset = (-1, 0);
+pub const DOWN: Offset = (1, 0);
+pub const LEFT: Offset = (0, -1);
+pub const RIGHT: Offset = (0, 1);
+
+// Both neighbourhoods are listed in reading order.
+pub const ORTHOGONAL: [Offset; 4] = [UP, LEFT, RIGHT, DOWN];
+pub const ADJACENT: [Offset; 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Grid<T> {
+    height: usize,
+    width: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Default + Clone> Grid<T> {
+    pub fn new(height: usize, width: usize) -> Self {
+        Self::filled(height, width, T::default())
+    }
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn filled(height: usize, width: usize, value: T) -> Self {
+        Self {
+            height,
+            width,
+            cells: vec![value; height * width],
+        }
+    }
+}
+
+impl<T> Grid<T> {
+    pub fn from_fn(height: usize, width: usize, mut f: impl FnMut(Pos) -> T) -> Self {
+        Self {
+            height,
+            width,
+            cells: iproduct!(0..height, 0..width).map(&mut f).collect(),
+        }
+    }
+
+    pub fn from_rows(rows: Vec<Vec<T>>) -> anyhow::Result<Self> {
+        let width = rows.first().map_or(0, Vec::len);
+        if let Some(row) = rows.iter().position(|r| r.len() != width) {
+            bail!(
+                "row {} has {} cells, expected {}",
+                row + 1,
+                rows[row].len(),
+                width
+            );
+        }
+        Ok(Self {
+            height: rows.len(),
+            width,
+            cells: rows.into_iter().flatten().collect(),
+        })
+    }
+
+    // A character map, one row per line; blank lines are errors like any other ragged row.
+    pub fn parse<E: Into<anyhow::Error>>(
+        day: u32,
+        input: &str,
+        mut f: impl FnMut(char) -> Result<T, E>,
+    ) -> anyhow::Result<Self> {
+        let mut width = None;
+        let rows = crate::parse::lines_with(day, input, |line| {
+            let row = line
+                .chars()
+                .map(|c| f(c).map_err(Into::into))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            match *width.get_or_insert(row.len()) {
+                width if width != row.len() => {
+                    bail!("expected {} cells, found {}", width, row.len())
+                }
+                _ => Ok(row),
+            }
+        })?;
+        Self::from_rows(rows)
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn contains(&self, (row, col): Pos) -> bool {
+        row < self.height && col < self.width
+    }
+
+    pub fn get(&self, pos: Pos) -> Option<&T> {
+        self.contains(pos)
+            .then(|| &self.cells[pos.0 * self.width + pos.1])
+    }
+
+    pub fn get_mut(&mut self, pos: Pos) -> Option<&mut T> {
+        self.contains(pos)
+            .then(|| &mut self.cells[pos.0 * self.width + pos.1])
+    }
+
+    pub fn offset(&self, pos: Pos, offset: Offset) -> Option<Pos> {
+        self.bounds().offset(pos, offset)
+    }
+
+    // Positions and neighbourhoods don't borrow the grid, so cells can be updated while walking them.
+    pub fn positions(&self) -> impl Iterator<Item = Pos> {
+        iproduct!(0..self.height, 0..self.width)
+    }
+
+    pub fn neighbours(&self, pos: Pos) -> impl Iterator<Item = Pos> {
+        let bounds = self.bounds();
+        ORTHOGONAL
+            .into_iter()
+            .filter_map(move |offset| bounds.offset(pos, offset))
+    }
+
+    pub fn adjacent(&self, pos: Pos) -> impl Iterator<Item = Pos> {
+        let bounds = self.bounds();
+        ADJACENT
+            .into_iter()
+            .filter_map(move |offset| bounds.offset(pos, offset))
+    }
+
+    fn bounds(&self) -> Bounds {
+        Bounds(self.height, self.width)
+    }
+
+    // The part of the height x width rectangle from top_left that lies on the grid.
+    pub fn rect(&self, (top, left): Pos, (height, width): Pos) -> impl Iterator<Item = Pos> {
+        let bottom = top.saturating_add(height).min(self.height);
+        let right = left.saturating_add(width).min(self.width);
+        iproduct!(top..bottom, left..right)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.cells.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.cells.iter_mut()
+    }
+
+    pub fn indexed(&self) -> impl Iterator<Item = (Pos, &T)> {
+        self.positions().zip(self.cells.iter())
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        // chunks panics on zero, and a grid with no columns has no cells anyway
+        self.cells.chunks(self.width.max(1))
+    }
+
+    pub fn row(&self, row: usize) -> &[T] {
+        &self.cells[row * self.width..(row + 1) * self.width]
+    }
+
+    pub fn row_mut(&mut self, row: usize) -> &mut [T] {
+        &mut self.cells[row * self.width..(row + 1) * self.width]
+    }
+
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> Grid<U> {
+        Grid {
+            height: self.height,
+            width: self.width,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+}
+
+impl<T> std::ops::Index<Pos> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, pos: Pos) -> &T {
+        self.get(pos)
+            .unwrap_or_else(|| panic!("{:?} is outside the grid", pos))
+    }
+}
+
+impl<T> std::ops::IndexMut<Pos> for Grid<T> {
+    fn index_mut(&mut self, pos: Pos) -> &mut T {
+        self.get_mut(pos)
+            .unwrap_or_else(|| panic!("{:?} is outside the grid", pos))
+    }
+}
+
+impl<T: std::fmt::Display> std::fmt::Display for Grid<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in self.rows() {
+            for cell in row {
+                write!(f, "{}", cell)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Bounds(usize, usize);
+
+impl Bounds {
+    fn offset(self, (row, col): Pos, (dr, dc): Offset) -> Option<Pos> {
+        let row = row.checked_add_signed(dr as isize)?;
+        let col = col.checked_add_signed(dc as isize)?;
+        (row < self.0 && col < self.1).then_some((row, col))
+    }
+}
+
+// Unbounded moves, for sparse grids and anything else keyed by signed points.
+pub fn offset((row, col): Point, (dr, dc): Offset) -> Option<Point> {
+    Some((row.checked_add(dr)?, col.checked_add(dc)?))
+}
+
+pub fn neighbours(point: Point) -> impl Iterator<Item = Point> {
+    ORTHOGONAL.into_iter().filter_map(move |d| offset(point, d))
+}
+
+pub fn adjacent(point: Point) -> impl Iterator<Item = Point> {
+    ADJACENT.into_iter().filter_map(move |d| offset(point, d))
+}
+
+// Unbounded in every direction, for maps discovered by walking or computed on demand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseGrid<T> {
+    cells: HashMap<Point, T>,
+}
+
+impl<T> Default for SparseGrid<T> {
+    fn default() -> Self {
+        Self {
+            cells: HashMap::new(),
+        }
+    }
+}
+
+impl<T> SparseGrid<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn get(&self, point: Point) -> Option<&T> {
+        self.cells.get(&point)
+    }
+
+    pub fn get_mut(&mut self, point: Point) -> Option<&mut T> {
+        self.cells.get_mut(&point)
+    }
+
+    pub fn insert(&mut self, point: Point, value: T) -> Option<T> {
+        self.cells.insert(point, value)
+    }
+
+    pub fn entry(&mut self, point: Point) -> std::collections::hash_map::Entry<'_, Point, T> {
+        self.cells.entry(point)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.cells.values()
+    }
+
+    // Occupied cells in reading order.
+    pub fn indexed(&self) -> impl Iterator<Item = (Point, &T)> {
+        self.cells
+            .iter()
+            .map(|(&point, value)| (point, value))
+            .sorted_by_key(|&(point, _)| point)
+    }
+
+    // The top-left and bottom-right corners of the occupied cells.
+    pub fn bounds(&self) -> Option<(Point, Point)> {
+        let span = |values: MinMaxResult<i64>| match values {
+            MinMaxResult::NoElements => None,
+            MinMaxResult::OneElement(v) => Some((v, v)),
+            MinMaxResult::MinMax(lo, hi) => Some((lo, hi)),
+        };
+        let (top, bottom) = span(self.cells.keys().map(|p| p.0).minmax())?;
+        let (left, right) = span(self.cells.keys().map(|p| p.1).minmax())?;
+        Some(((top, left), (bottom, right)))
+    }
+}
+
+impl<T> FromIterator<(Point, T)> for SparseGrid<T> {
+    fn from_iter<I: IntoIterator<Item = (Point, T)>>(iter: I) -> Self {
+        Self {
+            cells: iter.into_iter().collect(),
+        }
+    }
+}
+
+// Empty cells inside the bounds print as spaces.
+impl<T: std::fmt::Display> std::fmt::Display for SparseGrid<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some(((top, left), (bottom, right))) = self.bounds() else {
+            return Ok(());
+        };
+        for row in top..=bottom {
+            for col in left..=right {
+                match self.get((row, col)) {
+                    Some(cell) => write!(f, "{}", cell)?,
+                    None => write!(f, " ")?,
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod cells {
+    use super::*;
+
+    const MAP: &str = "#.#\n..#\n";
+
+    fn grid() -> Grid<char> {
+        Grid::parse(0, MAP, anyhow::Ok).unwrap()
+    }
+
+    #[test]
+    fn parses_and_prints() {
+        let grid = grid();
+        assert_eq!((grid.height(), grid.width()), (2, 3));
+        assert_eq!(grid[(1, 2)], '#');
+        assert_eq!(grid.get((2, 0)), None);
+        assert_eq!(grid.to_string(), MAP);
+        assert_eq!(
+            grid.rows().map(String::from_iter).collect_vec(),
+            vec!["#.#", "..#"]
+        );
+    }
+
+    #[test_case("#.#\n.#" => "day 0 line 2: \".#\": expected 3 cells, found 2")]
+    #[test_case("#.#\n\n#.#" => "day 0 line 2: \"\": expected 3 cells, found 0")]
+    #[test_case("#.#\n.x." => "day 0 line 2: \".x.\": bad cell 'x'")]
+    fn rejects(input: &str) -> String {
+        let cell = |c| match c {
+            '#' | '.' => Ok(c),
+            _ => Err(anyhow::anyhow!("bad cell {:?}", c)),
+        };
+        format!("{:#}", Grid::parse(0, input, cell).unwrap_err())
+    }
+
+    #[test]
+    fn neighbourhoods_stay_on_the_grid() {
+        let grid = grid();
+        assert_eq!(grid.neighbours((0, 0)).collect_vec(), vec![(0, 1), (1, 0)]);
+        assert_eq!(
+            grid.neighbours((1, 1)).collect_vec(),
+            vec![(0, 1), (1, 0), (1, 2)]
+        );
+        assert_eq!(
+            grid.adjacent((0, 2)).collect_vec(),
+            vec![(0, 1), (1, 1), (1, 2)]
+        );
+        assert_eq!(grid.offset((0, 0), UP), None);
+        assert_eq!(grid.offset((0, 0), (1, 2)), Some((1, 2)));
+        assert_eq!(grid.offset((0, usize::MAX), RIGHT), None);
+    }
+
+    #[test]
+    fn reading_order() {
+        let grid = Grid::from_fn(2, 2, |p| p);
+        assert_eq!(
+            grid.positions().collect_vec(),
+            grid.iter().copied().collect_vec()
+        );
+        assert_eq!(grid.rect((1, 1), (5, 5)).collect_vec(), vec![(1, 1)]);
+        assert_eq!(
+            Grid::from_rows(vec![vec![1], vec![]])
+                .unwrap_err()
+                .to_string(),
+            "row 2 has 0 cells, expected 1"
+        );
+    }
+
+    #[test]
+    fn sparse() {
+        let mut grid: SparseGrid<char> = [((-1, 2), '#'), ((1, 0), '#')].into_iter().collect();
+        assert_eq!(grid.bounds(), Some(((-1, 0), (1, 2))));
+        assert_eq!(grid.to_string(), "  #\n   \n#  \n");
+        grid.insert((0, 1), '.');
+        assert_eq!(
+            grid.indexed().map(|(p, _)| p).collect_vec(),
+            vec![(-1, 2), (0, 1), (1, 0)]
+        );
+        assert_eq!(
+            neighbours((i64::MIN, 0)).collect_vec(),
+            vec![(i64::MIN, -1), (i64::MIN, 1), (i64::MIN + 1, 0)]
+        );
+        assert_eq!(SparseGrid::<char>::new().to_string(), "");
+    }
+}
@@ -9,6 +9,7 @@ extern crate pretty_assertions;
 #[macro_use]
 extern crate test_case;
 
+pub mod answers;
 mod day01;
 mod day02;
 mod day03;
@@ -34,6 +35,11 @@ mod day22;
 mod day23;
 mod day24;
 mod day25;
-mod wasm;
+pub mod grid;
+mod parse;
+pub mod runner;
+pub mod simulation;
+pub mod synth;
+pub mod wasm;
 
 aoc_lib! { year = 2018 }
@@ -0,0 +1,76 @@
+use std::str::FromStr;
+
+use anyhow::Context;
+
+pub fn at(day: u32, line: usize, raw: &str) -> String {
+    format!("day {} line {}: {:?}", day, line, raw)
+}
+
+pub fn lines_from<T, E: Into<anyhow::Error>>(
+    day: u32,
+    first: usize,
+    input: &str,
+    mut f: impl FnMut(&str) -> Result<T, E>,
+) -> anyhow::Result<Vec<T>> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, raw)| {
+            f(raw)
+                .map_err(Into::into)
+                .with_context(|| at(day, first + i, raw))
+        })
+        .collect()
+}
+
+pub fn lines_with<T, E: Into<anyhow::Error>>(
+    day: u32,
+    input: &str,
+    f: impl FnMut(&str) -> Result<T, E>,
+) -> anyhow::Result<Vec<T>> {
+    lines_from(day, 1, input, f)
+}
+
+pub fn lines<T: FromStr>(day: u32, input: &str) -> anyhow::Result<Vec<T>>
+where
+    T::Err: Into<anyhow::Error>,
+{
+    lines_with(day, input, str::parse)
+}
+
+#[cfg(test)]
+pub fn round_trips<T>(values: impl IntoIterator<Item = T>)
+where
+    T: FromStr + std::fmt::Display + PartialEq + std::fmt::Debug,
+    T::Err: std::fmt::Debug,
+{
+    for value in values {
+        let text = value.to_string();
+        assert_eq!(text.parse::<T>().unwrap(), value, "{:?}", text);
+    }
+}
+
+// Rectangular rows of cells, at most `max` on a side, for grid inputs.
+#[cfg(test)]
+pub fn rows<S: proptest::strategy::Strategy + Clone>(
+    cell: S,
+    max: usize,
+) -> impl proptest::strategy::Strategy<Value = Vec<Vec<S::Value>>> {
+    use proptest::{collection::vec, strategy::Strategy};
+    (1..=max, 1..=max).prop_flat_map(move |(height, width)| vec(vec(cell.clone(), width), height))
+}
+
+#[cfg(test)]
+mod context {
+    use super::*;
+
+    #[test]
+    fn numbers_lines_from_one() {
+        assert_eq!(lines::<i32>(1, "+1\n-2").unwrap(), vec![1, -2]);
+        let error = lines::<i32>(1, "+1\n\n-2").unwrap_err();
+        assert_eq!(
+            format!("{:#}", error),
+            "day 1 line 2: \"\": cannot parse integer from empty string"
+        );
+    }
+}
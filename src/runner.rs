@@ -0,0 +1,244 @@
+use std::{
+    cell::{Cell, RefCell},
+    error::Error,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::Once,
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use aoc_runner::{ArcStr, Runner};
+
+use crate::aoc_factory::*;
+
+type Build = fn(ArcStr) -> Result<Box<dyn Runner>, Box<dyn Error>>;
+
+#[derive(Clone, Copy)]
+pub struct Solution {
+    pub day: u32,
+    pub part: u32,
+    pub name: Option<&'static str>,
+    build: Build,
+}
+
+macro_rules! solution {
+    ($day:literal, $part:literal, $build:ident) => {
+        Solution {
+            day: $day,
+            part: $part,
+            name: None,
+            build: Factory::$build,
+        }
+    };
+    ($day:literal, $part:literal, $name:literal, $build:ident) => {
+        Solution {
+            day: $day,
+            part: $part,
+            name: Some($name),
+            build: Factory::$build,
+        }
+    };
+}
+
+// Generated by build.rs from the #[aoc] attributes in the day modules.
+pub static SOLUTIONS: &[Solution] = include!(concat!(env!("OUT_DIR"), "/solutions.rs"));
+
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    pub answer: String,
+    pub generate: Duration,
+    pub run: Duration,
+}
+
+thread_local! {
+    static GUARDED: Cell<bool> = const { Cell::new(false) };
+    static LOCATION: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+// Panics inside `guarded` become errors, so the hook just notes where they
+// happened; panics anywhere else still reach the previous hook.
+fn install_hook() {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if GUARDED.get() {
+                LOCATION.set(info.location().map(|l| l.to_string()));
+            } else {
+                previous(info);
+            }
+        }));
+    });
+}
+
+fn guarded<T>(stage: &str, f: impl FnOnce() -> Result<T, Box<dyn Error>>) -> anyhow::Result<T> {
+    install_hook();
+    GUARDED.set(true);
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    GUARDED.set(false);
+    match result {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(e)) => {
+            // aoc-runner boxes our anyhow errors, which drops `{:#}`; walk the chain by hand
+            let chain = std::iter::successors(Some(&*e as &dyn Error), |&e| e.source())
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>();
+            Err(anyhow!("{} failed: {}", stage, chain.join(": ")))
+        }
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            match LOCATION.take() {
+                Some(location) => Err(anyhow!("{} panicked at {}: {}", stage, location, message)),
+                None => Err(anyhow!("{} panicked: {}", stage, message)),
+            }
+        }
+    }
+}
+
+impl Solution {
+    pub fn label(&self) -> String {
+        match self.name {
+            Some(name) => format!("day{} part{} ({})", self.day, self.part, name),
+            None => format!("day{} part{}", self.day, self.part),
+        }
+    }
+
+    pub fn generate(&self, input: &str) -> anyhow::Result<()> {
+        guarded("generating", || (self.build)(ArcStr::from(input))).map(drop)
+    }
+
+    pub fn run(&self, input: &str) -> anyhow::Result<Outcome> {
+        let input = ArcStr::from(input);
+        let start = Instant::now();
+        let runner = guarded("generating", || (self.build)(input))?;
+        let generated = Instant::now();
+        let answer = guarded("running", || runner.try_run())?.to_string();
+        Ok(Outcome {
+            answer,
+            generate: generated - start,
+            run: generated.elapsed(),
+        })
+    }
+}
+
+pub struct Comparison {
+    pub day: u32,
+    pub part: u32,
+    pub runs: Vec<(&'static Solution, anyhow::Result<Outcome>)>,
+}
+
+impl Comparison {
+    pub fn agreed(&self) -> Option<&str> {
+        let mut answers = self
+            .runs
+            .iter()
+            .map(|(_, run)| run.as_ref().ok().map(|o| o.answer.as_str()));
+        let first = answers.next()??;
+        answers.all(|a| a == Some(first)).then_some(first)
+    }
+}
+
+pub fn compare(day: u32, part: Option<u32>, input: &str) -> Vec<Comparison> {
+    let mut comparisons: Vec<Comparison> = vec![];
+    for solution in find(day, part) {
+        let run = (solution, solution.run(input));
+        match comparisons.last_mut() {
+            Some(c) if c.part == solution.part => c.runs.push(run),
+            _ => comparisons.push(Comparison {
+                day,
+                part: solution.part,
+                runs: vec![run],
+            }),
+        }
+    }
+    comparisons
+}
+
+pub fn days() -> impl Iterator<Item = u32> {
+    let mut days = SOLUTIONS.iter().map(|s| s.day).collect::<Vec<_>>();
+    days.dedup();
+    days.into_iter()
+}
+
+pub fn find(day: u32, part: Option<u32>) -> impl Iterator<Item = &'static Solution> {
+    SOLUTIONS
+        .iter()
+        .filter(move |s| s.day == day && part.is_none_or(|p| s.part == p))
+}
+
+pub fn input_path(dir: impl AsRef<Path>, day: u32) -> PathBuf {
+    dir.as_ref().join(format!("day{}.txt", day))
+}
+
+#[cfg(test)]
+mod solutions {
+    use super::*;
+
+    #[test]
+    fn registry() {
+        assert_eq!(days().collect::<Vec<_>>(), (1..=25).collect::<Vec<_>>());
+        assert_eq!(find(5, Some(1)).count(), 2);
+        assert_eq!(find(10, None).count(), 1);
+        assert_eq!(
+            find(21, Some(2)).map(|s| s.label()).collect::<Vec<_>>(),
+            vec!["day21 part2", "day21 part2 (compiled)"]
+        );
+    }
+
+    #[test]
+    fn runs() {
+        let solution = find(1, Some(1)).next().unwrap();
+        assert_eq!(solution.run("+1\n-2\n+3\n").unwrap().answer, "2");
+    }
+
+    #[test]
+    fn compares_implementations() {
+        let comparisons = compare(5, None, "dabAcCaCBAcCcaDA");
+        assert_eq!(
+            comparisons.iter().map(|c| c.part).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(comparisons[0].runs.len(), 2);
+        assert_eq!(comparisons[0].agreed(), Some("10"));
+        assert_eq!(comparisons[1].agreed(), Some("4"));
+    }
+
+    #[test]
+    fn detects_disagreement() {
+        let mut comparison = compare(5, Some(1), "dabAcCaCBAcCcaDA").remove(0);
+        comparison.runs[1].1.as_mut().unwrap().answer = "11".to_string();
+        assert_eq!(comparison.agreed(), None);
+        comparison.runs[1].1 = Err(anyhow!("boom"));
+        assert_eq!(comparison.agreed(), None);
+    }
+
+    #[test]
+    fn reports_failures() {
+        let solution = find(1, Some(1)).next().unwrap();
+        let error = solution.run("+1\nseven\n").unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "generating failed: day 1 line 2: \"seven\": invalid digit found in string"
+        );
+    }
+
+    #[test]
+    fn reports_panic_location() {
+        let error = guarded("running", || -> Result<(), Box<dyn Error>> {
+            panic!("boom")
+        })
+        .unwrap_err()
+        .to_string();
+        assert!(
+            error.starts_with("running panicked at src/runner.rs:"),
+            "{}",
+            error
+        );
+        assert!(error.ends_with(": boom"), "{}", error);
+    }
+}
@@ -0,0 +1,174 @@
+use std::{collections::HashMap, hash::Hash};
+
+pub trait Simulation {
+    // Two states with equal keys must evolve identically.
+    type Key: Hash + Eq;
+
+    fn step(&mut self);
+
+    fn is_finished(&self) -> bool {
+        false
+    }
+
+    fn state_key(&self) -> Self::Key;
+
+    fn render(&self) -> String;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    pub start: usize,
+    pub period: usize,
+}
+
+// Each driver returns the number of steps it took.
+pub fn run_until<S: Simulation>(sim: &mut S, mut done: impl FnMut(&S) -> bool) -> usize {
+    let mut steps = 0;
+    while !sim.is_finished() && !done(sim) {
+        sim.step();
+        steps += 1;
+    }
+    steps
+}
+
+pub fn run_to_end<S: Simulation>(sim: &mut S) -> usize {
+    run_until(sim, |_| false)
+}
+
+pub fn run_for<S: Simulation>(sim: &mut S, n: usize) -> usize {
+    let mut steps = 0;
+    run_until(sim, |_| {
+        steps += 1;
+        steps > n
+    })
+}
+
+// Steps until a state repeats, leaving the simulation start + period steps
+// along; None if it finishes or reaches the limit first.
+pub fn find_cycle<S: Simulation>(sim: &mut S, limit: usize) -> Option<Cycle> {
+    let mut seen = HashMap::new();
+    let mut steps = 0;
+    loop {
+        if let Some(start) = seen.insert(sim.state_key(), steps) {
+            return Some(Cycle {
+                start,
+                period: steps - start,
+            });
+        }
+        if steps == limit || sim.is_finished() {
+            return None;
+        }
+        sim.step();
+        steps += 1;
+    }
+}
+
+// Runs n steps, jumping over whole periods once a state repeats.
+pub fn skip_cycles<S: Simulation>(sim: &mut S, n: usize) -> Option<Cycle> {
+    let cycle = find_cycle(sim, n)?;
+    let done = cycle.start + cycle.period;
+    run_for(sim, (n - done) % cycle.period);
+    Some(cycle)
+}
+
+// The starting render and one more after each of up to n steps.
+pub fn frames<S: Simulation>(sim: &mut S, n: usize) -> Vec<String> {
+    let mut frames = vec![sim.render()];
+    for _ in 0..n {
+        if run_for(sim, 1) == 0 {
+            break;
+        }
+        frames.push(sim.render());
+    }
+    frames
+}
+
+#[cfg(test)]
+mod drivers {
+    use super::*;
+
+    // Counts up by one and wraps to `restart` after `wrap`; stops at `end` if that is ever reached.
+    struct Counter {
+        value: usize,
+        restart: usize,
+        wrap: usize,
+        end: usize,
+        steps: usize,
+    }
+
+    impl Counter {
+        fn new(restart: usize, wrap: usize, end: usize) -> Self {
+            Self {
+                value: 0,
+                restart,
+                wrap,
+                end,
+                steps: 0,
+            }
+        }
+    }
+
+    impl Simulation for Counter {
+        type Key = usize;
+
+        fn step(&mut self) {
+            self.value = if self.value == self.wrap {
+                self.restart
+            } else {
+                self.value + 1
+            };
+            self.steps += 1;
+        }
+
+        fn is_finished(&self) -> bool {
+            self.value == self.end
+        }
+
+        fn state_key(&self) -> usize {
+            self.value
+        }
+
+        fn render(&self) -> String {
+            self.value.to_string()
+        }
+    }
+
+    #[test]
+    fn runs() {
+        let mut counter = Counter::new(0, 100, 7);
+        assert_eq!(run_for(&mut counter, 3), 3);
+        assert_eq!(run_to_end(&mut counter), 4);
+        assert_eq!(counter.value, 7);
+        assert_eq!(run_for(&mut counter, 3), 0);
+        let mut counter = Counter::new(0, 100, 7);
+        assert_eq!(run_until(&mut counter, |c| c.value == 5), 5);
+    }
+
+    #[test]
+    fn cycles() {
+        let mut counter = Counter::new(3, 6, 100);
+        assert_eq!(
+            find_cycle(&mut counter, 1000),
+            Some(Cycle {
+                start: 3,
+                period: 4
+            })
+        );
+        assert_eq!(counter.steps, 7);
+        assert_eq!(find_cycle(&mut Counter::new(3, 6, 100), 5), None);
+        assert_eq!(find_cycle(&mut Counter::new(0, 100, 7), 1000), None);
+
+        // 0 1 2 3 4 5 6 3 4 5 6 ...
+        for n in [2, 7, 10, 1_000_000_001] {
+            let mut counter = Counter::new(3, 6, 100);
+            skip_cycles(&mut counter, n);
+            assert_eq!(counter.value, if n < 7 { n } else { 3 + (n - 3) % 4 });
+        }
+    }
+
+    #[test]
+    fn captures_frames() {
+        assert_eq!(frames(&mut Counter::new(0, 100, 2), 5), vec!["0", "1", "2"]);
+        assert_eq!(frames(&mut Counter::new(0, 100, 9), 2), vec!["0", "1", "2"]);
+    }
+}
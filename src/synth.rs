@@ -0,0 +1,816 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::Write,
+};
+
+use itertools::Itertools;
+
+use crate::wasm::{Instruction, Number, Op};
+
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    // splitmix64
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    pub fn range(&mut self, lo: i64, hi: i64) -> i64 {
+        lo + (self.next_u64() % (hi - lo + 1) as u64) as i64
+    }
+
+    pub fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+
+    pub fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len())]
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+pub fn input(day: u32, scale: usize, seed: u64) -> Option<String> {
+    let rng = &mut Rng::new(seed);
+    let scale = scale.max(1);
+    let generate = match day {
+        1 => day1,
+        2 => day2,
+        3 => day3,
+        4 => day4,
+        5 => day5,
+        6 => day6,
+        7 => day7,
+        8 => day8,
+        9 => day9,
+        10 => day10,
+        11 => day11,
+        12 => day12,
+        13 => day13,
+        14 => day14,
+        15 => day15,
+        16 => day16,
+        17 => day17,
+        18 => day18,
+        19 => day19,
+        20 => day20,
+        21 => day21,
+        22 => day22,
+        23 => day23,
+        24 => day24,
+        25 => day25,
+        _ => return None,
+    };
+    Some(generate(rng, scale))
+}
+
+// A dozen seeded inputs across scales 1 to 3, for property tests.
+pub fn samples(day: u32) -> impl Iterator<Item = String> {
+    (0..12).filter_map(move |seed| input(day, 1 + seed as usize % 3, seed))
+}
+
+const NOISE: &[char] = &[
+    '0', '1', '9', '-', '+', ',', ' ', '\n', '#', '.', '<', '>', '=', ':', '(', ')', ';', '|', '/',
+    '\\', '^', 'v', 'x', 'y', 'E', 'G', '\u{e9}',
+];
+
+// Arbitrary text over the characters the puzzle formats care about.
+pub fn noise(rng: &mut Rng, len: usize) -> String {
+    (0..len).map(|_| rng.pick(NOISE)).collect()
+}
+
+const EXTREMES: &[&str] = &["0", "1", "4294967296", "99999999999999999999"];
+
+// A valid input with a few random deletions, insertions, overwrites, extreme
+// numbers or a truncation.
+pub fn mangle(rng: &mut Rng, input: &str) -> String {
+    let mut chars = input.chars().collect_vec();
+    for _ in 0..1 + rng.below(4) {
+        let at = rng.below(chars.len() + 1);
+        match rng.below(5) {
+            0 if at < chars.len() => drop(chars.remove(at)),
+            1 => chars.insert(at, rng.pick(NOISE)),
+            2 if at < chars.len() => chars[at] = rng.pick(NOISE),
+            3 => chars.truncate(at),
+            _ => {
+                let Some(start) = (at..chars.len()).find(|&i| chars[i].is_ascii_digit()) else {
+                    continue;
+                };
+                let end = (start..chars.len())
+                    .find(|&i| !chars[i].is_ascii_digit())
+                    .unwrap_or(chars.len());
+                chars.splice(start..end, rng.pick(EXTREMES).chars());
+            }
+        }
+    }
+    chars.into_iter().collect()
+}
+
+// Frequency changes summing to zero, so part 2 repeats within one pass.
+fn day1(rng: &mut Rng, scale: usize) -> String {
+    let mut changes = (0..scale * 20)
+        .map(|_| rng.range(-50, 50))
+        .filter(|&c| c != 0)
+        .collect_vec();
+    let sum: i64 = changes.iter().sum();
+    if sum != 0 {
+        changes.push(-sum);
+    }
+    changes.iter().map(|c| format!("{:+}\n", c)).collect()
+}
+
+fn letters(rng: &mut Rng, n: usize) -> String {
+    (0..n)
+        .map(|_| (b'a' + rng.below(26) as u8) as char)
+        .collect()
+}
+
+// Random box ids plus one pair that differs in a single position.
+fn day2(rng: &mut Rng, scale: usize) -> String {
+    let mut ids = (0..scale * 20).map(|_| letters(rng, 26)).collect_vec();
+    let mut twin = ids[0].clone().into_bytes();
+    let i = rng.below(twin.len());
+    twin[i] = b'a' + (twin[i] - b'a' + 1 + rng.below(25) as u8) % 26;
+    ids.push(String::from_utf8(twin).unwrap());
+    rng.shuffle(&mut ids);
+    ids.iter().map(|id| format!("{}\n", id)).collect()
+}
+
+// Overlapping claims in the left of the fabric and one isolated claim on the right.
+fn day3(rng: &mut Rng, scale: usize) -> String {
+    let mut claims = (0..scale * 20)
+        .map(|_| {
+            let (w, h) = (rng.range(1, 30), rng.range(1, 30));
+            (rng.range(0, 900 - w), rng.range(0, 1000 - h), w, h)
+        })
+        .collect_vec();
+    claims.push((rng.range(910, 960), rng.range(0, 960), 30, 30));
+    rng.shuffle(&mut claims);
+    claims
+        .iter()
+        .enumerate()
+        .map(|(i, (x, y, w, h))| format!("#{} @ {},{}: {}x{}\n", i + 1, x, y, w, h))
+        .collect()
+}
+
+// One shift per night, with sorted minutes but shuffled lines.
+fn day4(rng: &mut Rng, scale: usize) -> String {
+    let guards = (0..scale.clamp(2, 20))
+        .map(|_| rng.range(1, 3500))
+        .collect_vec();
+    let mut lines = vec![];
+    for night in 0..scale * 10 {
+        let date = format!("1518-{:02}-{:02}", 1 + night / 28 % 12, 1 + night % 28);
+        lines.push(format!(
+            "[{} 00:{:02}] Guard #{} begins shift",
+            date,
+            rng.below(5),
+            rng.pick(&guards)
+        ));
+        let mut minute = 5 + rng.below(10);
+        while minute < 55 {
+            let wake = rng.range(minute as i64 + 1, 59) as usize;
+            lines.push(format!("[{} 00:{:02}] falls asleep", date, minute));
+            lines.push(format!("[{} 00:{:02}] wakes up", date, wake));
+            minute = wake + 1 + rng.below(20);
+        }
+    }
+    rng.shuffle(&mut lines);
+    lines.iter().map(|l| format!("{}\n", l)).collect()
+}
+
+// Starts with two distinct units so removing one type never leaves an empty polymer.
+fn day5(rng: &mut Rng, scale: usize) -> String {
+    let mut polymer = String::from("ab");
+    for _ in 0..scale * 100 {
+        let unit = (b'a' + rng.below(26) as u8) as char;
+        polymer.push(if rng.chance(50) {
+            unit.to_ascii_uppercase()
+        } else {
+            unit
+        });
+    }
+    polymer
+}
+
+// A centre point boxed in by four neighbours keeps at least one area finite.
+fn day6(rng: &mut Rng, scale: usize) -> String {
+    let size = 50 + scale as i64 * 10;
+    let (c, d) = (size / 2, rng.range(3, 10));
+    let mut points = vec![(c, c), (c - d, c), (c + d, c), (c, c - d), (c, c + d)];
+    points.extend((0..scale * 10).map(|_| (rng.range(0, size), rng.range(0, size))));
+    rng.shuffle(&mut points);
+    points
+        .iter()
+        .unique()
+        .map(|(x, y)| format!("{}, {}\n", x, y))
+        .collect()
+}
+
+// Edges always point forward in a random permutation of the steps.
+fn day7(rng: &mut Rng, scale: usize) -> String {
+    let mut steps = ('A'..='Z').take(scale.clamp(2, 26)).collect_vec();
+    rng.shuffle(&mut steps);
+    let mut edges = BTreeSet::new();
+    for (i, &to) in steps.iter().enumerate().skip(1) {
+        edges.insert((steps[rng.below(i)], to));
+        for _ in 0..rng.below(3) {
+            edges.insert((steps[rng.below(i)], to));
+        }
+    }
+    let mut edges = edges.into_iter().collect_vec();
+    rng.shuffle(&mut edges);
+    edges
+        .iter()
+        .map(|(a, b)| format!("Step {} must be finished before step {} can begin.\n", a, b))
+        .collect()
+}
+
+fn tree(rng: &mut Rng, budget: &mut usize, out: &mut Vec<usize>) {
+    let children = if *budget > 0 { rng.below(4) } else { 0 };
+    *budget = budget.saturating_sub(children);
+    let metadata = 1 + rng.below(3);
+    out.extend([children, metadata]);
+    for _ in 0..children {
+        tree(rng, budget, out);
+    }
+    out.extend((0..metadata).map(|_| 1 + rng.below(9)));
+}
+
+fn day8(rng: &mut Rng, scale: usize) -> String {
+    let mut values = vec![];
+    tree(rng, &mut (scale * 20), &mut values);
+    values.iter().join(" ")
+}
+
+fn day9(rng: &mut Rng, scale: usize) -> String {
+    format!(
+        "{} players; last marble is worth {} points",
+        rng.range(5, 30 + scale as i64),
+        rng.range(25, 1000 * scale as i64)
+    )
+}
+
+// Points that converge onto a small box at a random moment.
+fn day10(rng: &mut Rng, scale: usize) -> String {
+    let time = rng.range(10, 10_000);
+    (0..scale * 30)
+        .map(|_| {
+            let (x, y) = (rng.range(0, 8 * scale as i64), rng.range(0, 9));
+            let (dx, dy) = (rng.range(-5, 5), rng.range(-5, 5));
+            let (dx, dy) = if (dx, dy) == (0, 0) { (1, 1) } else { (dx, dy) };
+            format!(
+                "position=<{:>6}, {:>6}> velocity=<{:>2}, {:>2}>\n",
+                x - time * dx,
+                y - time * dy,
+                dx,
+                dy
+            )
+        })
+        .collect()
+}
+
+// The grid is always 300 square, so scale only widens the range of serials.
+fn day11(rng: &mut Rng, scale: usize) -> String {
+    rng.range(1, 10_000 * scale as i64 - 1).to_string()
+}
+
+fn pots(pots: impl IntoIterator<Item = bool>) -> String {
+    pots.into_iter()
+        .map(|p| if p { '#' } else { '.' })
+        .collect()
+}
+
+// Every rule copies one pot of its window, so the plants drift at a constant rate.
+fn day12(rng: &mut Rng, scale: usize) -> String {
+    let source = rng.below(5);
+    let initial = pots((0..scale * 25).map(|_| rng.chance(50)));
+    let rules = (0..32)
+        .map(|bits: usize| {
+            let window = (0..5).map(|i| bits & (16 >> i) != 0).collect_vec();
+            format!("{} => {}\n", pots(window.clone()), pots([window[source]]))
+        })
+        .collect::<String>();
+    format!("initial state: {}\n\n{}", initial, rules)
+}
+
+// Separate loops of track; carts on each loop cancel out in opposing pairs
+// except for a single survivor on the first loop.
+fn day13(rng: &mut Rng, scale: usize) -> String {
+    let loops = scale.clamp(1, 10);
+    let height = 12;
+    let mut rows = vec![String::new(); height];
+    for n in 0..loops {
+        let (w, h) = (
+            rng.range(6, 20) as usize,
+            rng.range(4, height as i64) as usize,
+        );
+        let mut cells = vec![vec![' '; w]; height];
+        cells[0][1..w - 1].fill('-');
+        cells[h - 1][1..w - 1].fill('-');
+        for row in cells.iter_mut().take(h - 1).skip(1) {
+            row[0] = '|';
+            row[w - 1] = '|';
+        }
+        cells[0][0] = '/';
+        cells[0][w - 1] = '\\';
+        cells[h - 1][0] = '\\';
+        cells[h - 1][w - 1] = '/';
+        // clockwise and anticlockwise facings for each straight cell
+        let mut perimeter = vec![];
+        for col in 1..w - 1 {
+            perimeter.push((0, col, '>', '<'));
+            perimeter.push((h - 1, col, '<', '>'));
+        }
+        for row in 1..h - 1 {
+            perimeter.push((row, w - 1, 'v', '^'));
+            perimeter.push((row, 0, '^', 'v'));
+        }
+        rng.shuffle(&mut perimeter);
+        let pairs = if n == 0 {
+            1 + rng.below(3)
+        } else {
+            rng.below(3)
+        };
+        let survivors = usize::from(n == 0);
+        let facings = (0..pairs)
+            .flat_map(|_| [true, false])
+            .chain((0..survivors).map(|_| rng.chance(50)));
+        for ((row, col, clockwise, anticlockwise), facing) in perimeter.into_iter().zip(facings) {
+            cells[row][col] = if facing { clockwise } else { anticlockwise };
+        }
+        for (row, cells) in rows.iter_mut().zip(cells) {
+            row.extend(cells);
+            row.push(' ');
+        }
+    }
+    rows.iter().map(|r| format!("{}\n", r)).collect()
+}
+
+fn recipes(len: usize) -> Vec<u8> {
+    let mut board = vec![3u8, 7];
+    let (mut a, mut b) = (0, 1);
+    while board.len() < len {
+        let sum = board[a] + board[b];
+        if sum >= 10 {
+            board.push(sum / 10);
+        }
+        board.push(sum % 10);
+        a = (a + 1 + board[a] as usize) % board.len();
+        b = (b + 1 + board[b] as usize) % board.len();
+    }
+    board
+}
+
+// Digits copied out of the scoreboard itself, so part 2 always finds them.
+fn day14(rng: &mut Rng, scale: usize) -> String {
+    let digits = 5;
+    let start = 1 + rng.below(scale * 1000);
+    let board = recipes(start + digits);
+    let mut text = board[start..start + digits].iter().join("");
+    if text.starts_with('0') {
+        text.replace_range(..1, "1");
+    }
+    text
+}
+
+// Open cave with random pillars; pockets cut off from the largest region are
+// walled in so every unit can reach every other.
+fn day15(rng: &mut Rng, scale: usize) -> String {
+    let size = 7 + scale.min(25);
+    let mut map = vec![vec!['#'; size]; size];
+    for row in map.iter_mut().take(size - 1).skip(1) {
+        for cell in row.iter_mut().take(size - 1).skip(1) {
+            if !rng.chance(20) {
+                *cell = '.';
+            }
+        }
+    }
+    let open = (0..size)
+        .cartesian_product(0..size)
+        .filter(|&(r, c)| map[r][c] == '.')
+        .collect_vec();
+    let mut regions: Vec<HashSet<(usize, usize)>> = vec![];
+    for &start in &open {
+        if regions.iter().any(|r| r.contains(&start)) {
+            continue;
+        }
+        let mut region = HashSet::from([start]);
+        let mut todo = vec![start];
+        while let Some((r, c)) = todo.pop() {
+            for next in [(r - 1, c), (r + 1, c), (r, c - 1), (r, c + 1)] {
+                if map[next.0][next.1] == '.' && region.insert(next) {
+                    todo.push(next);
+                }
+            }
+        }
+        regions.push(region);
+    }
+    let largest = regions.iter().max_by_key(|r| r.len()).unwrap();
+    let mut cells = largest.iter().copied().sorted().collect_vec();
+    for &(r, c) in &open {
+        if !largest.contains(&(r, c)) {
+            map[r][c] = '#';
+        }
+    }
+    rng.shuffle(&mut cells);
+    let units = (2 + scale).min(cells.len());
+    for (i, &(r, c)) in cells.iter().take(units).enumerate() {
+        map[r][c] = match i {
+            0 => 'G',
+            1 => 'E',
+            _ if rng.chance(50) => 'G',
+            _ => 'E',
+        };
+    }
+    map.iter()
+        .map(|row| format!("{}\n", String::from_iter(row)))
+        .collect()
+}
+
+// Samples keep coming until every opcode number is pinned to its op, then a
+// straight-line program avoiding multiplication so registers grow slowly.
+fn day16(rng: &mut Rng, scale: usize) -> String {
+    let mut ops = Op::puzzle().collect_vec();
+    rng.shuffle(&mut ops);
+    let mut candidates = vec![Op::puzzle().collect::<HashSet<_>>(); ops.len()];
+    let mut samples = vec![];
+    while samples.len() < scale * 10 || candidates.iter().any(|c| c.len() > 1) {
+        let number = rng.below(ops.len());
+        let instr = [number, rng.below(4), rng.below(4), rng.below(4)];
+        let before: [Number; 4] = std::array::from_fn(|_| rng.below(4));
+        let run = |op: Op| {
+            let mut after = before;
+            Instruction::new(op, instr[1], instr[2], instr[3]).apply(&mut after);
+            after
+        };
+        let after = run(ops[number]);
+        candidates[number].retain(|&op| run(op) == after);
+        samples.push(format!(
+            "Before: [{}]\n{}\nAfter:  [{}]\n",
+            before.iter().join(", "),
+            instr.iter().join(" "),
+            after.iter().join(", ")
+        ));
+    }
+    use Op::*;
+    let safe = [
+        Addi, Bani, Banr, Bori, Borr, Setr, Seti, Gtir, Gtri, Gtrr, Eqir, Eqri, Eqrr,
+    ];
+    let program = (0..scale * 20)
+        .map(|_| {
+            let op = rng.pick(&safe);
+            let number = ops.iter().position(|&o| o == op).unwrap();
+            let b = if op == Addi {
+                rng.below(16)
+            } else {
+                rng.below(4)
+            };
+            format!("{} {} {} {}\n", number, rng.below(4), b, rng.below(4))
+        })
+        .collect::<String>();
+    format!("{}\n\n\n{}", samples.join("\n"), program)
+}
+
+// Disjoint buckets laid out on a grid straddling the spring at x=500; the
+// corners of the top row are always filled so clay lies on both sides of it.
+fn day17(rng: &mut Rng, scale: usize) -> String {
+    let (columns, rows) = (2 + scale.min(8), 1 + scale.min(10));
+    let left = 500 - columns as i64 / 2 * 14;
+    let mut veins = vec![];
+    for (row, column) in (0..rows).cartesian_product(0..columns) {
+        let corner = row == 0 && (column == 0 || column == columns - 1);
+        if !corner && !rng.chance(70) {
+            continue;
+        }
+        let (w, h) = (rng.range(2, 10), rng.range(2, 8));
+        let x = left + column as i64 * 14 + rng.range(1, 11 - w);
+        let y = 1 + row as i64 * 12 + rng.range(1, 9 - h);
+        veins.push(format!("x={}, y={}..{}", x, y, y + h));
+        veins.push(format!("x={}, y={}..{}", x + w, y, y + h));
+        veins.push(format!("y={}, x={}..{}", y + h, x, x + w));
+    }
+    rng.shuffle(&mut veins);
+    veins.iter().map(|v| format!("{}\n", v)).collect()
+}
+
+fn day18(rng: &mut Rng, scale: usize) -> String {
+    let size = 10 + scale.min(40);
+    (0..size)
+        .map(|_| {
+            let row = (0..size)
+                .map(|_| match rng.below(10) {
+                    0..=4 => '.',
+                    5..=7 => '|',
+                    _ => '#',
+                })
+                .collect::<String>();
+            format!("{}\n", row)
+        })
+        .collect()
+}
+
+// Renames every register but r0, which holds the answer.
+fn elfcode(rng: &mut Rng, template: &str, ip: Number) -> String {
+    let mut registers = (1..6).collect_vec();
+    rng.shuffle(&mut registers);
+    let map = |r: Number| if r == 0 { 0 } else { registers[r - 1] };
+    let mut program = format!("#ip {}\n", map(ip));
+    for text in template.lines() {
+        let (op, a, b, c) = text.split_ascii_whitespace().collect_tuple().unwrap();
+        let op: Op = op.parse().unwrap();
+        let (ra, rb) = op.reads();
+        let number = |v: &str| v.parse::<Number>().unwrap();
+        let a = if ra { map(number(a)) } else { number(a) };
+        let b = if rb { map(number(b)) } else { number(b) };
+        writeln!(program, "{} {} {} {}", op, a, b, map(number(c))).unwrap();
+    }
+    program
+}
+
+// The divisor sum loop over r1 = a * b + c, then r1 * d + e when r0 starts at 1.
+// Scale raises the bounds on a, b and d, and so on both loops.
+fn day19(rng: &mut Rng, scale: usize) -> String {
+    let scale = scale as i64;
+    let template = format!(
+        "addi 5 16 5
+seti 1 0 2
+seti 1 0 3
+mulr 2 3 4
+eqrr 4 1 4
+addr 4 5 5
+addi 5 1 5
+addr 2 0 0
+addi 3 1 3
+gtrr 3 1 4
+addr 5 4 5
+seti 2 0 5
+addi 2 1 2
+gtrr 2 1 4
+addr 4 5 5
+seti 1 0 5
+mulr 5 5 5
+seti {} 0 1
+muli 1 {} 1
+addi 1 {} 1
+addr 5 0 5
+seti 0 0 5
+muli 1 {} 1
+addi 1 {} 1
+seti 0 0 0
+seti 0 0 5",
+        rng.range(2, 10 + 10 * scale),
+        rng.range(2, 10 + 10 * scale),
+        rng.range(0, 20),
+        rng.range(1000, 10_000 + 10_000 * scale),
+        rng.range(0, 100)
+    );
+    elfcode(rng, &template, 5)
+}
+
+// Hashes bytes of r2 into r1 until it matches r0; the seed, multiplier and
+// mask width vary. The values cycle within the mask, so larger scales pick
+// wider masks for longer loops, up to 24 bits.
+fn day21(rng: &mut Rng, scale: usize) -> String {
+    let mask = (1 << rng.range(12 + 4 * scale.min(3) as i64, 24)) - 1;
+    let template = format!(
+        "seti 0 0 1
+bori 1 65536 2
+seti {} 0 1
+bani 2 255 3
+addr 1 3 1
+bani 1 {mask} 1
+muli 1 {} 1
+bani 1 {mask} 1
+gtir 256 2 3
+addr 3 4 4
+addi 4 1 4
+seti 22 0 4
+seti 0 0 3
+addi 3 1 5
+muli 5 256 5
+gtrr 5 2 5
+addr 5 4 4
+addi 4 1 4
+seti 20 0 4
+addi 3 1 3
+seti 12 0 4
+setr 3 0 2
+seti 2 0 4
+eqrr 1 0 3
+addr 3 4 4
+seti 0 0 4",
+        rng.below(1 << 24),
+        rng.range(1, 1 << 16) * 2 + 1,
+    );
+    elfcode(rng, &template, 4)
+}
+
+fn directions(rng: &mut Rng, min: i64, max: i64) -> String {
+    let n = rng.range(min, max);
+    (0..n).map(|_| rng.pick(&['N', 'E', 'S', 'W'])).collect()
+}
+
+fn route(rng: &mut Rng, depth: usize, budget: &mut usize) -> String {
+    let mut path = directions(rng, 1, 4);
+    while *budget > 0 && depth > 0 && rng.chance(60) {
+        *budget -= 1;
+        let branches = 1 + rng.below(3);
+        let mut options = (0..branches)
+            .map(|_| route(rng, depth - 1, budget))
+            .collect_vec();
+        if rng.chance(30) {
+            options.push(String::new());
+        }
+        write!(path, "({})", options.join("|")).unwrap();
+        path.push_str(&directions(rng, 0, 3));
+    }
+    path
+}
+
+fn day20(rng: &mut Rng, scale: usize) -> String {
+    format!("^{}$", route(rng, 6, &mut (scale * 10)))
+}
+
+// The mouth and the target share the depth's region type, which must allow
+// the torch or the target can never be reached.
+fn day22(rng: &mut Rng, scale: usize) -> String {
+    let depth = loop {
+        let depth = rng.range(1000, 12000);
+        if depth % 3 != 1 {
+            break depth;
+        }
+    };
+    format!(
+        "depth: {}\ntarget: {},{}",
+        depth,
+        rng.range(1, 5 + scale as i64),
+        rng.range(1, 10 + 5 * scale as i64)
+    )
+}
+
+fn day23(rng: &mut Rng, scale: usize) -> String {
+    let spread = 1000 * scale as i64;
+    (0..scale * 20)
+        .map(|_| {
+            format!(
+                "pos=<{},{},{}>, r={}\n",
+                rng.range(-spread, spread),
+                rng.range(-spread, spread),
+                rng.range(-spread, spread),
+                rng.range(spread / 10, spread)
+            )
+        })
+        .collect()
+}
+
+// Neither army is immune to a damage type its enemy uses. A few infection units
+// with attacks big enough to kill an immune unit every hit mean battles never
+// stall, while large immune groups always survive the first round, so a high
+// enough boost wins.
+fn day24(rng: &mut Rng, scale: usize) -> String {
+    let damages = ["bludgeoning", "cold", "fire", "radiation", "slashing"];
+    let groups = 1 + scale.min(9);
+    let mut initiatives = (1..=2 * groups).collect_vec();
+    rng.shuffle(&mut initiatives);
+    let hitpoints = (0..2 * groups)
+        .map(|_| rng.range(1000, 10000))
+        .collect_vec();
+    let top = *hitpoints[..groups].iter().max().unwrap();
+    let attacks = (0..2).map(|_| {
+        let mut types = damages.to_vec();
+        rng.shuffle(&mut types);
+        types.truncate(2 + rng.below(2));
+        types
+    });
+    let attacks = attacks.collect_vec();
+    let mut text = String::new();
+    for (army, name) in ["Immune System", "Infection"].iter().enumerate() {
+        writeln!(text, "{}:", name).unwrap();
+        for group in 0..groups {
+            let index = army * groups + group;
+            let enemy = &attacks[1 - army];
+            let mut weak = vec![];
+            let mut immune = vec![];
+            for damage in damages {
+                match rng.below(5) {
+                    0 => weak.push(damage),
+                    1 if !enemy.contains(&damage) => immune.push(damage),
+                    _ => {}
+                }
+            }
+            let modifiers = [("immune to", &immune), ("weak to", &weak)]
+                .iter()
+                .filter(|(_, list)| !list.is_empty())
+                .map(|(label, list)| format!("{} {}", label, list.join(", ")))
+                .join("; ");
+            let modifiers = if modifiers.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", modifiers)
+            };
+            let (units, attack) = match army {
+                0 => (rng.range(250, 2000), rng.range(1, 30)),
+                _ => (rng.range(1, 10), top + rng.range(0, 1000)),
+            };
+            writeln!(
+                text,
+                "{} units each with {} hit points{} with an attack that does {} {} damage at initiative {}",
+                units,
+                hitpoints[index],
+                modifiers,
+                attack,
+                rng.pick(&attacks[army]),
+                initiatives[index]
+            )
+            .unwrap();
+        }
+        text.push('\n');
+    }
+    text
+}
+
+fn day25(rng: &mut Rng, scale: usize) -> String {
+    (0..scale * 50)
+        .map(|_| format!("{}\n", (0..4).map(|_| rng.range(-8, 8)).join(",")))
+        .collect()
+}
+
+#[cfg(test)]
+mod synthetic {
+    use super::*;
+    use crate::runner::{compare, SOLUTIONS};
+
+    #[test]
+    fn deterministic() {
+        for day in 1..=25 {
+            assert_eq!(input(day, 2, 7), input(day, 2, 7), "day {}", day);
+        }
+        assert_ne!(input(3, 2, 7), input(3, 2, 8));
+        assert_eq!(input(26, 1, 1), None);
+    }
+
+    #[test_case(1)]
+    #[test_case(2)]
+    #[test_case(3)]
+    fn solvable(seed: u64) {
+        for day in 1..=25 {
+            let input = input(day, 2, seed).unwrap();
+            for comparison in compare(day, None, &input) {
+                assert!(
+                    comparison.agreed().is_some(),
+                    "day {} part {} seed {}: {:?}",
+                    day,
+                    comparison.part,
+                    seed,
+                    comparison
+                        .runs
+                        .iter()
+                        .map(|(_, r)| r.as_ref().map(|o| &o.answer).map_err(|e| e.to_string()))
+                        .collect_vec()
+                );
+            }
+        }
+    }
+
+    // A quick seeded smoke test run with every `cargo test`; the cargo-fuzz
+    // targets in fuzz/ search for panicking inputs properly.
+    #[test]
+    fn parsers_survive_mangled_inputs() {
+        let rng = &mut Rng::new(0);
+        for solution in SOLUTIONS {
+            let valid = input(solution.day, 1, 0).unwrap();
+            for _ in 0..200 {
+                let len = rng.below(60);
+                for text in [mangle(rng, &valid), noise(rng, len)] {
+                    if let Err(e) = solution.generate(&text) {
+                        let message = e.to_string();
+                        assert!(
+                            !message.starts_with("generating panicked"),
+                            "{}: {} on {:?}",
+                            solution.label(),
+                            message,
+                            text
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
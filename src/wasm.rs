@@ -1,8 +1,25 @@
-use itertools::Itertools;
+use std::{
+    ops::ControlFlow,
+    time::{Duration, Instant},
+};
 
-type Number = usize;
+use strum_macros::{Display, EnumIter, EnumString};
 
-#[derive(Debug, Clone, Copy)]
+pub mod asm;
+pub mod compile;
+pub mod dataflow;
+pub mod debug;
+pub mod disasm;
+pub mod optimize;
+pub mod profile;
+pub mod snapshot;
+pub mod symbolic;
+pub mod trace;
+
+pub type Number = usize;
+
+#[derive(Debug, Display, EnumString, EnumIter, Clone, Copy, PartialEq, Eq, Hash)]
+#[strum(serialize_all = "lowercase")]
 pub enum Op {
     Addr,
     Addi,
@@ -20,118 +37,510 @@ pub enum Op {
     Eqir,
     Eqri,
     Eqrr,
+    Divr,
+    Divi,
+    Modr,
+    Modi,
+    Shl,
+    Shr,
+    Nop,
+    Halt,
 }
 
-impl std::str::FromStr for Op {
-    type Err = anyhow::Error;
+#[derive(Debug, Display, EnumString, Default, Clone, Copy, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum Dialect {
+    #[default]
+    Puzzle,
+    Extended,
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+impl Op {
+    pub fn puzzle() -> impl Iterator<Item = Op> + Clone {
+        use strum::IntoEnumIterator;
+        Op::iter().filter(|op| !op.is_extension())
+    }
+
+    pub fn is_extension(&self) -> bool {
+        use Op::*;
+        matches!(self, Divr | Divi | Modr | Modi | Shl | Shr | Nop | Halt)
+    }
+
+    pub fn allowed(&self, dialect: Dialect) -> bool {
+        dialect == Dialect::Extended || !self.is_extension()
+    }
+
+    pub fn reads(&self) -> (bool, bool) {
+        use Op::*;
+        match self {
+            Addr | Mulr | Banr | Borr | Gtrr | Eqrr | Divr | Modr => (true, true),
+            Addi | Muli | Bani | Bori | Gtri | Eqri | Setr | Divi | Modi | Shl | Shr => {
+                (true, false)
+            }
+            Seti | Nop | Halt => (false, false),
+            Gtir | Eqir => (false, true),
+        }
+    }
+
+    pub fn writes(&self) -> bool {
+        !matches!(self, Op::Nop | Op::Halt)
+    }
+
+    // Total: arithmetic wraps, and dividing by zero or shifting by the word
+    // size or more gives 0. Use Cpu::try_apply to see those as faults.
+    pub fn apply(&self, a: Number, b: Number, r: &[Number]) -> Number {
+        let (ra, rb) = self.reads();
+        let a = if ra { r[a] } else { a };
+        let b = if rb { r[b] } else { b };
+        self.evaluate(a, b, Arithmetic::Wrapping).unwrap_or(0)
+    }
+
+    pub fn evaluate(&self, a: Number, b: Number, arithmetic: Arithmetic) -> Option<Number> {
         use Op::*;
-        match s {
-            "addr" => Ok(Addr),
-            "addi" => Ok(Addi),
-            "mulr" => Ok(Mulr),
-            "muli" => Ok(Muli),
-            "banr" => Ok(Banr),
-            "bani" => Ok(Bani),
-            "borr" => Ok(Borr),
-            "bori" => Ok(Bori),
-            "setr" => Ok(Setr),
-            "seti" => Ok(Seti),
-            "gtir" => Ok(Gtir),
-            "gtri" => Ok(Gtri),
-            "gtrr" => Ok(Gtrr),
-            "eqir" => Ok(Eqir),
-            "eqri" => Ok(Eqri),
-            "eqrr" => Ok(Eqrr),
-            _ => anyhow::bail!("Invalid op {}", s),
-        }
-    }
-}
-
-#[derive(Debug, Clone, Copy)]
+        match self {
+            Addr | Addi => arithmetic.add(a, b),
+            Mulr | Muli => arithmetic.mul(a, b),
+            Banr | Bani => Some(a & b),
+            Borr | Bori => Some(a | b),
+            Setr | Seti => Some(a),
+            Gtir | Gtri | Gtrr => Some(Number::from(a > b)),
+            Eqir | Eqri | Eqrr => Some(Number::from(a == b)),
+            Divr | Divi => a.checked_div(b),
+            Modr | Modi => a.checked_rem(b),
+            Shl => a.checked_shl(u32::try_from(b).ok()?),
+            Shr => a.checked_shr(u32::try_from(b).ok()?),
+            Nop | Halt => None,
+        }
+    }
+}
+
+#[derive(Debug, Display, EnumString, EnumIter, Default, Clone, Copy, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum Arithmetic {
+    Wrapping,
+    #[default]
+    Checked,
+    Saturating,
+}
+
+impl Arithmetic {
+    fn add(&self, a: Number, b: Number) -> Option<Number> {
+        match self {
+            Arithmetic::Wrapping => Some(a.wrapping_add(b)),
+            Arithmetic::Checked => a.checked_add(b),
+            Arithmetic::Saturating => Some(a.saturating_add(b)),
+        }
+    }
+
+    fn mul(&self, a: Number, b: Number) -> Option<Number> {
+        match self {
+            Arithmetic::Wrapping => Some(a.wrapping_mul(b)),
+            Arithmetic::Checked => a.checked_mul(b),
+            Arithmetic::Saturating => Some(a.saturating_mul(b)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuError {
+    InvalidRegister { pc: Number, register: Number },
+    Overflow { pc: Number, instr: Instruction },
+    DivisionByZero { pc: Number, instr: Instruction },
+    CycleBudget { cycles: usize },
+    PcOutOfRange { pc: i32 },
+}
+
+impl std::fmt::Display for CpuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CpuError::InvalidRegister { pc, register } => {
+                write!(f, "invalid register {} at pc {}", register, pc)
+            }
+            CpuError::Overflow { pc, instr } => {
+                write!(
+                    f,
+                    "arithmetic overflow in {:?} at pc {}",
+                    instr.to_string(),
+                    pc
+                )
+            }
+            CpuError::DivisionByZero { pc, instr } => {
+                write!(
+                    f,
+                    "division by zero in {:?} at pc {}",
+                    instr.to_string(),
+                    pc
+                )
+            }
+            CpuError::CycleBudget { cycles } => {
+                write!(f, "cycle budget exhausted after {} cycles", cycles)
+            }
+            CpuError::PcOutOfRange { pc } => write!(f, "pc {} is outside the program", pc),
+        }
+    }
+}
+
+impl std::error::Error for CpuError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Instruction {
-    op: Op,
+    pub op: Op,
     pub a: Number,
-    b: Number,
-    c: Number,
+    pub b: Number,
+    pub c: Number,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    BlankLine,
+    UnknownOp,
+    BadNumber,
+    MissingOperand,
+    ExtraToken,
+    BadRegister { registers: usize },
+    DuplicateIp,
+    Extension,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub token: String,
+    pub kind: ParseErrorKind,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        match self.kind {
+            ParseErrorKind::BlankLine => write!(f, "blank line"),
+            ParseErrorKind::UnknownOp => write!(f, "unknown op {:?}", self.token),
+            ParseErrorKind::BadNumber => write!(f, "expected a number, found {:?}", self.token),
+            ParseErrorKind::MissingOperand => write!(f, "missing operand"),
+            ParseErrorKind::ExtraToken => write!(f, "unexpected {:?}", self.token),
+            ParseErrorKind::BadRegister { registers } => write!(
+                f,
+                "register {} is outside {} registers",
+                self.token, registers
+            ),
+            ParseErrorKind::DuplicateIp => write!(f, "second #ip directive"),
+            ParseErrorKind::Extension => {
+                write!(f, "{:?} is an extension, not a puzzle op", self.token)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+struct Line<'a> {
+    number: usize,
+    text: &'a str,
+}
+
+impl<'a> Line<'a> {
+    fn tokens(&self) -> impl Iterator<Item = (usize, &'a str)> + '_ {
+        let code = self.text.split(';').next().unwrap();
+        code.split_ascii_whitespace().map(move |token| {
+            (
+                token.as_ptr() as usize - self.text.as_ptr() as usize + 1,
+                token,
+            )
+        })
+    }
+
+    fn error(&self, column: usize, token: &str, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            line: self.number,
+            column,
+            token: token.to_string(),
+            kind,
+        }
+    }
+
+    fn number(&self, (column, token): (usize, &str)) -> Result<Number, ParseError> {
+        token
+            .parse()
+            .map_err(|_| self.error(column, token, ParseErrorKind::BadNumber))
+    }
+
+    fn register(
+        &self,
+        (column, token): (usize, &str),
+        registers: Option<usize>,
+    ) -> Result<Number, ParseError> {
+        let register = self.number((column, token))?;
+        match registers {
+            Some(registers) if register >= registers => {
+                Err(self.error(column, token, ParseErrorKind::BadRegister { registers }))
+            }
+            _ => Ok(register),
+        }
+    }
+
+    fn end(&self, mut tokens: impl Iterator<Item = (usize, &'a str)>) -> Result<(), ParseError> {
+        match tokens.next() {
+            Some((column, token)) => Err(self.error(column, token, ParseErrorKind::ExtraToken)),
+            None => Ok(()),
+        }
+    }
+
+    fn instruction(
+        &self,
+        registers: Option<usize>,
+        dialect: Dialect,
+    ) -> Result<Instruction, ParseError> {
+        let mut tokens = self.tokens();
+        let mut next = || {
+            tokens.next().ok_or_else(|| {
+                let column = self.text.trim_end().len() + 1;
+                self.error(column, "", ParseErrorKind::MissingOperand)
+            })
+        };
+        let (column, token) = next()?;
+        let op: Op = token
+            .parse()
+            .map_err(|_| self.error(column, token, ParseErrorKind::UnknownOp))?;
+        if !op.allowed(dialect) {
+            return Err(self.error(column, token, ParseErrorKind::Extension));
+        }
+        let (ra, rb) = op.reads();
+        let operand = |token, register| {
+            if register {
+                self.register(token, registers)
+            } else {
+                self.number(token)
+            }
+        };
+        let a = operand(next()?, ra)?;
+        let b = operand(next()?, rb)?;
+        let c = operand(next()?, true)?;
+        self.end(tokens)?;
+        Ok(Instruction { op, a, b, c })
+    }
 }
 
 impl std::str::FromStr for Instruction {
-    type Err = anyhow::Error;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let chunks = s.split_ascii_whitespace().collect_vec();
-        Ok(Instruction {
-            op: chunks[0].parse()?,
-            a: chunks[1].parse()?,
-            b: chunks[2].parse()?,
-            c: chunks[3].parse()?,
-        })
+        Instruction::parse(s, Dialect::default())
     }
 }
 
-#[derive(Debug, Default, Clone)]
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {} {}", self.op, self.a, self.b, self.c)
+    }
+}
+
+impl Instruction {
+    pub fn new(op: Op, a: Number, b: Number, c: Number) -> Self {
+        Self { op, a, b, c }
+    }
+
+    pub fn parse(text: &str, dialect: Dialect) -> Result<Self, ParseError> {
+        Line { number: 1, text }.instruction(None, dialect)
+    }
+
+    // The first register outside a file of `count` that running this would
+    // touch, checked in the same order as Cpu::try_apply.
+    pub fn invalid_register(&self, pc_reg: Option<usize>, count: usize) -> Option<Number> {
+        let (ra, rb) = self.op.reads();
+        pc_reg
+            .into_iter()
+            .chain([self.c])
+            .chain(ra.then_some(self.a))
+            .chain(rb.then_some(self.b))
+            .find(|&register| register >= count)
+    }
+
+    pub fn apply(&self, registers: &mut [Number]) {
+        if self.op.writes() {
+            registers[self.c] = self.op.apply(self.a, self.b, registers);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Cpu {
-    pub registers: [Number; 6],
+    pub registers: Vec<Number>,
     pc: i32,
-    pub pc_reg: usize,
+    pub pc_reg: Option<usize>,
     pub cycles: usize,
     pub program: Vec<Instruction>,
+    pub arithmetic: Arithmetic,
+    pub fault: Option<CpuError>,
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new(6)
+    }
 }
 
 impl std::str::FromStr for Cpu {
-    type Err = anyhow::Error;
+    type Err = ParseError;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        let mut pc_reg = 0;
+        Cpu::parse(input, Cpu::default().registers.len(), Dialect::default())
+    }
+}
+
+impl std::fmt::Display for Cpu {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(pc_reg) = self.pc_reg {
+            writeln!(f, "#ip {}", pc_reg)?;
+        }
+        for instr in &self.program {
+            writeln!(f, "{}", instr)?;
+        }
+        Ok(())
+    }
+}
+
+impl Cpu {
+    pub fn parse(input: &str, registers: usize, dialect: Dialect) -> Result<Self, ParseError> {
+        let mut pc_reg = None;
         let mut program = vec![];
-        for line in input.lines() {
-            if let Some(offset) = line.strip_prefix("#ip ") {
-                pc_reg = offset.parse()?;
-            } else {
-                program.push(line.parse()?)
+        for (number, text) in input.lines().enumerate() {
+            let line = Line {
+                number: number + 1,
+                text,
+            };
+            let mut tokens = line.tokens();
+            match tokens.next() {
+                None if text.trim().is_empty() => {
+                    return Err(line.error(1, "", ParseErrorKind::BlankLine))
+                }
+                None => {}
+                Some((column, "#ip")) => {
+                    if pc_reg.is_some() {
+                        return Err(line.error(column, "#ip", ParseErrorKind::DuplicateIp));
+                    }
+                    let register = tokens.next().ok_or_else(|| {
+                        line.error(
+                            text.trim_end().len() + 1,
+                            "",
+                            ParseErrorKind::MissingOperand,
+                        )
+                    })?;
+                    pc_reg = Some(line.register(register, Some(registers))?);
+                    line.end(tokens)?;
+                }
+                Some(_) => program.push(line.instruction(Some(registers), dialect)?),
             }
         }
 
         Ok(Cpu {
             pc_reg,
             program,
-            ..Cpu::default()
+            ..Cpu::new(registers)
         })
     }
-}
 
-impl Cpu {
+    pub fn new(registers: usize) -> Self {
+        Self {
+            registers: vec![0; registers],
+            pc: 0,
+            pc_reg: None,
+            cycles: 0,
+            program: vec![],
+            arithmetic: Arithmetic::default(),
+            fault: None,
+        }
+    }
+
+    // A fault halts the cpu and is kept in `fault` for the caller to report.
     pub fn apply(&mut self) {
-        let instr = self.program[self.pc as usize];
-        self.registers[self.pc_reg] = self.pc as Number;
+        if let Err(e) = self.try_apply() {
+            self.fault = Some(e);
+        }
+    }
 
-        use Op::*;
-        self.registers[instr.c] = match instr.op {
-            Addr => self.registers[instr.a] + self.registers[instr.b],
-            Addi => self.registers[instr.a] + instr.b,
-            Mulr => self.registers[instr.a] * self.registers[instr.b],
-            Muli => self.registers[instr.a] * instr.b,
-            Banr => self.registers[instr.a] & self.registers[instr.b],
-            Bani => self.registers[instr.a] & instr.b,
-            Borr => self.registers[instr.a] | self.registers[instr.b],
-            Bori => self.registers[instr.a] | instr.b,
-            Setr => self.registers[instr.a],
-            Seti => instr.a,
-            Gtir => usize::from(instr.a > self.registers[instr.b]),
-            Gtri => usize::from(self.registers[instr.a] > instr.b),
-            Gtrr => usize::from(self.registers[instr.a] > self.registers[instr.b]),
-            Eqir => usize::from(instr.a == self.registers[instr.b]),
-            Eqri => usize::from(self.registers[instr.a] == instr.b),
-            Eqrr => usize::from(self.registers[instr.a] == self.registers[instr.b]),
+    pub fn try_apply(&mut self) -> Result<(), CpuError> {
+        let instr = usize::try_from(self.pc)
+            .ok()
+            .and_then(|pc| self.program.get(pc))
+            .copied()
+            .ok_or(CpuError::PcOutOfRange { pc: self.pc })?;
+        let pc = self.pc as Number;
+        let count = self.registers.len();
+        let register = |register: Number| {
+            if register < count {
+                Ok(register)
+            } else {
+                Err(CpuError::InvalidRegister { pc, register })
+            }
+        };
+
+        let pc_reg = self.pc_reg.map(register).transpose()?;
+        let c = register(instr.c)?;
+        if let Some(pc_reg) = pc_reg {
+            self.registers[pc_reg] = pc;
+        }
+        let (ra, rb) = instr.op.reads();
+        let a = if ra {
+            self.registers[register(instr.a)?]
+        } else {
+            instr.a
+        };
+        let b = if rb {
+            self.registers[register(instr.b)?]
+        } else {
+            instr.b
+        };
+        if matches!(instr.op, Op::Divr | Op::Divi | Op::Modr | Op::Modi) && b == 0 {
+            return Err(CpuError::DivisionByZero { pc, instr });
+        }
+        if instr.op.writes() {
+            self.registers[c] = instr
+                .op
+                .evaluate(a, b, self.arithmetic)
+                .ok_or(CpuError::Overflow { pc, instr })?;
+        }
+        let next = pc_reg.map_or(pc, |pc_reg| self.registers[pc_reg]);
+        self.pc = if instr.op == Op::Halt {
+            self.program.len() as i32
+        } else {
+            i32::try_from(next)
+                .ok()
+                .and_then(|pc| pc.checked_add(1))
+                .unwrap_or(i32::MAX)
         };
-        self.pc = self.registers[self.pc_reg] as i32 + 1;
         self.cycles += 1;
+        Ok(())
+    }
+
+    pub fn try_run(&mut self, budget: usize) -> Result<(), CpuError> {
+        let start = self.cycles;
+        while !self.halted() {
+            if self.cycles - start >= budget {
+                return Err(CpuError::CycleBudget {
+                    cycles: self.cycles,
+                });
+            }
+            self.try_apply()?;
+        }
+        Ok(())
+    }
+
+    pub fn pc(&self) -> i32 {
+        self.pc
+    }
+
+    pub fn is_ip(&self, register: usize) -> bool {
+        self.pc_reg == Some(register)
+    }
+
+    // The plain run paths stop on a fault and keep it; this hands it back.
+    pub fn healthy(&self) -> Result<(), CpuError> {
+        self.fault.map_or(Ok(()), Err)
     }
 
     pub fn halted(&self) -> bool {
-        self.pc < 0 || self.pc as usize >= self.program.len()
+        self.fault.is_some() || self.pc < 0 || self.pc as usize >= self.program.len()
     }
 
     pub fn run(&mut self) {
@@ -141,11 +550,406 @@ impl Cpu {
     }
 
     pub fn run_till_pc(&mut self, target: usize) {
+        self.execute(Some(target), &Limits::default(), |_, _, _| {
+            ControlFlow::Continue(())
+        });
+    }
+
+    pub fn run_for(&mut self, cycles: usize) -> StopReason {
+        let limits = Limits {
+            max_cycles: Some(self.cycles + cycles),
+            ..Limits::default()
+        };
+        match self.execute(None, &limits, |_, _, _| ControlFlow::Continue(())) {
+            StopReason::CycleLimit => StopReason::Yielded,
+            reason => reason,
+        }
+    }
+
+    pub fn run_limited(&mut self, limits: &Limits) -> StopReason {
+        self.execute(None, limits, |_, _, _| ControlFlow::Continue(()))
+    }
+
+    pub fn run_till_pc_limited(&mut self, target: usize, limits: &Limits) -> StopReason {
+        self.execute(Some(target), limits, |_, _, _| ControlFlow::Continue(()))
+    }
+
+    // The observer sees each instruction's pc, the registers it read (with
+    // the ip bound) and the cpu after it ran, and can interrupt the run.
+    pub fn run_observed(
+        &mut self,
+        limits: &Limits,
+        observe: impl FnMut(Number, &[Number], &Cpu) -> ControlFlow<()>,
+    ) -> StopReason {
+        self.execute(None, limits, observe)
+    }
+
+    fn execute(
+        &mut self,
+        target: Option<usize>,
+        limits: &Limits,
+        mut observe: impl FnMut(Number, &[Number], &Cpu) -> ControlFlow<()>,
+    ) -> StopReason {
+        let started = Instant::now();
+        let mut before = self.registers.clone();
         while !self.halted() {
+            if limits.max_cycles.is_some_and(|max| self.cycles >= max) {
+                return StopReason::CycleLimit;
+            }
+            if let Some(max) = limits.max_time {
+                if self.cycles.is_multiple_of(4096) && started.elapsed() >= max {
+                    return StopReason::TimeLimit;
+                }
+            }
+            let pc = self.pc as Number;
+            before.copy_from_slice(&self.registers);
+            if let Some(pc_reg) = self.pc_reg {
+                before[pc_reg] = pc;
+            }
             self.apply();
-            if self.pc == target as i32 {
-                return;
+            if self.fault.is_none() && observe(pc, &before, self).is_break() {
+                return StopReason::Interrupted;
+            }
+            if let Some(target) = target {
+                if self.pc == target as i32 {
+                    return StopReason::ReachedPc(target);
+                }
             }
         }
+        match self.fault {
+            Some(fault) => StopReason::Fault(fault),
+            None => StopReason::Halted,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub max_cycles: Option<usize>,
+    pub max_time: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    Halted,
+    Yielded,
+    ReachedPc(usize),
+    CycleLimit,
+    TimeLimit,
+    Fault(CpuError),
+    Interrupted,
+}
+
+#[cfg(test)]
+mod limits {
+    use super::*;
+
+    fn spinner() -> Cpu {
+        "#ip 0\nseti 0 0 1\nseti 0 0 0".parse().unwrap()
+    }
+
+    #[test]
+    fn run_for_interleaves() {
+        let mut a: Cpu = include_str!("day19_example.txt").parse().unwrap();
+        let mut b = spinner();
+        assert_eq!(a.run_for(2), StopReason::Yielded);
+        assert_eq!(b.run_for(2), StopReason::Yielded);
+        assert_eq!(a.run_for(2), StopReason::Yielded);
+        assert_eq!(b.run_for(1000), StopReason::Yielded);
+        assert_eq!(a.run_for(1000), StopReason::Halted);
+        assert_eq!((a.cycles, b.cycles), (5, 1002));
+        assert_eq!(a.registers[0], 6);
+    }
+
+    #[test]
+    fn cycle_limit() {
+        let mut cpu = spinner();
+        let limits = Limits {
+            max_cycles: Some(500),
+            ..Limits::default()
+        };
+        assert_eq!(cpu.run_limited(&limits), StopReason::CycleLimit);
+        assert_eq!(cpu.cycles, 500);
+        assert_eq!(cpu.run_till_pc_limited(3, &limits), StopReason::CycleLimit);
+    }
+
+    #[test]
+    fn time_limit() {
+        let mut cpu = spinner();
+        let limits = Limits {
+            max_time: Some(Duration::from_millis(10)),
+            ..Limits::default()
+        };
+        assert_eq!(cpu.run_limited(&limits), StopReason::TimeLimit);
+    }
+
+    #[test]
+    fn reached_pc() {
+        let mut cpu: Cpu = include_str!("day19_example.txt").parse().unwrap();
+        assert_eq!(
+            cpu.run_till_pc_limited(4, &Limits::default()),
+            StopReason::ReachedPc(4)
+        );
+        assert_eq!(cpu.run_limited(&Limits::default()), StopReason::Halted);
+    }
+}
+
+#[cfg(test)]
+mod try_apply {
+    use super::*;
+
+    fn cpu(program: &str) -> Cpu {
+        Cpu::parse(program, 6, Dialect::Extended).unwrap()
+    }
+
+    #[test]
+    fn invalid_register() {
+        let mut cpu = cpu("#ip 5\nseti 1 0 0");
+        cpu.program.push(Instruction::new(Op::Addr, 0, 9, 1));
+        assert_eq!(cpu.try_apply(), Ok(()));
+        assert_eq!(
+            cpu.try_apply(),
+            Err(CpuError::InvalidRegister { pc: 1, register: 9 })
+        );
+        assert_eq!(
+            cpu.try_run(10),
+            Err(CpuError::InvalidRegister { pc: 1, register: 9 })
+        );
+    }
+
+    #[test_case(Arithmetic::Checked => None)]
+    #[test_case(Arithmetic::Wrapping => Some(usize::MAX - 1))]
+    #[test_case(Arithmetic::Saturating => Some(usize::MAX))]
+    fn arithmetic(arithmetic: Arithmetic) -> Option<Number> {
+        let mut cpu = cpu("#ip 5\nmuli 0 2 0");
+        cpu.arithmetic = arithmetic;
+        cpu.registers[0] = usize::MAX;
+        match cpu.try_run(10) {
+            Ok(()) => Some(cpu.registers[0]),
+            Err(CpuError::Overflow { pc: 0, .. }) => None,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    #[test_case(Arithmetic::Checked => (usize::MAX, 0))]
+    #[test_case(Arithmetic::Wrapping => (usize::MAX - 1, 1))]
+    #[test_case(Arithmetic::Saturating => (usize::MAX, 1))]
+    fn plain_path_respects_arithmetic(arithmetic: Arithmetic) -> (Number, usize) {
+        let mut cpu = cpu("#ip 5\nmuli 0 2 0");
+        cpu.arithmetic = arithmetic;
+        cpu.registers[0] = usize::MAX;
+        cpu.run();
+        (cpu.registers[0], cpu.cycles)
+    }
+
+    #[test]
+    fn faults_stop_the_plain_path() {
+        let mut cpu = cpu("#ip 5\nseti 3 0 0\ndivi 0 0 1\nseti 4 0 0");
+        let fault = CpuError::DivisionByZero {
+            pc: 1,
+            instr: Instruction::new(Op::Divi, 0, 0, 1),
+        };
+        assert_eq!(
+            cpu.run_limited(&Limits::default()),
+            StopReason::Fault(fault)
+        );
+        assert!(cpu.halted());
+        assert_eq!(
+            (cpu.fault, cpu.registers[0], cpu.cycles),
+            (Some(fault), 3, 1)
+        );
+    }
+
+    #[test]
+    fn cycle_budget() {
+        let mut cpu = cpu("#ip 0\nseti 0 0 1\nseti 0 0 0");
+        assert_eq!(cpu.try_run(100), Err(CpuError::CycleBudget { cycles: 100 }));
+        assert_eq!(cpu.try_run(5), Err(CpuError::CycleBudget { cycles: 105 }));
+    }
+
+    #[test]
+    fn division_by_zero() {
+        let mut cpu = cpu("#ip 5\nmodi 0 0 1");
+        assert_eq!(
+            cpu.try_apply(),
+            Err(CpuError::DivisionByZero {
+                pc: 0,
+                instr: Instruction::new(Op::Modi, 0, 0, 1)
+            })
+        );
+    }
+
+    #[test]
+    fn halt() {
+        let source = "#ip 5\nseti 3 0 0\nhalt 0 0 0\nseti 4 0 0";
+        let mut plain = cpu(source);
+        let mut checked = cpu(source);
+        let mut compiled = cpu(source);
+        plain.run();
+        assert_eq!(checked.try_run(10), Ok(()));
+        compile::Compiled::new(&compiled.clone(), &[]).run(&mut compiled);
+        for cpu in [plain, checked, compiled] {
+            assert!(cpu.halted());
+            assert_eq!((cpu.registers[0], cpu.registers[5], cpu.cycles), (3, 1, 2));
+        }
+    }
+
+    #[test]
+    fn pc_out_of_range() {
+        let mut cpu = cpu(include_str!("day19_example.txt"));
+        assert_eq!(cpu.try_run(100), Ok(()));
+        assert_eq!(cpu.registers[0], 6);
+        assert_eq!(cpu.try_apply(), Err(CpuError::PcOutOfRange { pc: 7 }));
+    }
+}
+
+#[cfg(test)]
+mod op {
+    use super::*;
+    use strum::IntoEnumIterator;
+
+    #[test]
+    fn extensions() {
+        assert_eq!(Op::puzzle().count(), 16);
+        assert!(Op::iter().take(16).all(|op| !op.is_extension()));
+        assert!(Op::iter().skip(16).all(|op| op.is_extension()));
+        assert!(!Op::Halt.allowed(Dialect::Puzzle));
+        assert!(Op::Halt.allowed(Dialect::Extended));
+    }
+
+    #[test]
+    fn mnemonics_round_trip() {
+        for op in Op::iter() {
+            assert_eq!(op.to_string().parse::<Op>().unwrap(), op);
+        }
+    }
+
+    #[test_case("mulr 2 1 2", [3, 2, 1, 1] => vec![3, 2, 2, 1])]
+    #[test_case("addi 2 1 2", [3, 2, 1, 1] => vec![3, 2, 2, 1])]
+    #[test_case("seti 2 1 2", [3, 2, 1, 1] => vec![3, 2, 2, 1])]
+    #[test_case("divi 0 2 1", [7, 0, 0, 0] => vec![7, 3, 0, 0])]
+    #[test_case("modr 0 2 3", [7, 0, 4, 0] => vec![7, 0, 4, 3])]
+    #[test_case("shl 0 3 1", [7, 0, 0, 0] => vec![7, 56, 0, 0])]
+    #[test_case("shr 0 1 1", [7, 0, 0, 0] => vec![7, 3, 0, 0])]
+    #[test_case("nop 0 0 0", [7, 0, 0, 0] => vec![7, 0, 0, 0])]
+    #[test_case("divi 0 0 1", [7, 9, 0, 0] => vec![7, 0, 0, 0])]
+    #[test_case("modr 0 2 3", [7, 0, 0, 9] => vec![7, 0, 0, 0])]
+    #[test_case("shl 0 64 1", [7, 9, 0, 0] => vec![7, 0, 0, 0])]
+    #[test_case("shr 0 99 1", [7, 9, 0, 0] => vec![7, 0, 0, 0])]
+    #[test_case("addi 0 2 1", [usize::MAX, 0, 0, 0] => vec![usize::MAX, 1, 0, 0])]
+    fn apply(instr: &str, registers: [Number; 4]) -> Vec<Number> {
+        let mut registers = registers.to_vec();
+        Instruction::parse(instr, Dialect::Extended)
+            .unwrap()
+            .apply(&mut registers);
+        registers
+    }
+}
+
+#[cfg(test)]
+mod parse {
+    use super::*;
+    use crate::synth::{samples, Rng};
+    use strum::IntoEnumIterator;
+
+    fn error(input: &str) -> (usize, usize, String, ParseErrorKind) {
+        let e = input.parse::<Cpu>().unwrap_err();
+        (e.line, e.column, e.token, e.kind)
+    }
+
+    #[test]
+    fn comments() {
+        let cpu: Cpu = "; program\n#ip 1 ; ip\nseti 5 0 0 ; start\n  ;\naddi 0 1 0"
+            .parse()
+            .unwrap();
+        assert_eq!(cpu.pc_reg, Some(1));
+        assert_eq!(
+            cpu.program,
+            vec![
+                Instruction::new(Op::Seti, 5, 0, 0),
+                Instruction::new(Op::Addi, 0, 1, 0)
+            ]
+        );
+    }
+
+    #[test_case("seti 5 0" => (1, 9, "".to_string(), ParseErrorKind::MissingOperand))]
+    #[test_case("seti 5 0 1 2" => (1, 12, "2".to_string(), ParseErrorKind::ExtraToken))]
+    #[test_case("#ip 0\nfoo 1 2 3" => (2, 1, "foo".to_string(), ParseErrorKind::UnknownOp))]
+    #[test_case("#ip 0\nseti x 2 3" => (2, 6, "x".to_string(), ParseErrorKind::BadNumber))]
+    #[test_case("#ip 0\n\nseti 1 2 3" => (2, 1, "".to_string(), ParseErrorKind::BlankLine))]
+    #[test_case("#ip 0\n#ip 1" => (2, 1, "#ip".to_string(), ParseErrorKind::DuplicateIp))]
+    #[test_case("#ip 6" => (1, 5, "6".to_string(), ParseErrorKind::BadRegister { registers: 6 }))]
+    #[test_case("#ip 1 2" => (1, 7, "2".to_string(), ParseErrorKind::ExtraToken))]
+    #[test_case("addr 1 7 0" => (1, 8, "7".to_string(), ParseErrorKind::BadRegister { registers: 6 }))]
+    #[test_case("seti 7 0 9" => (1, 10, "9".to_string(), ParseErrorKind::BadRegister { registers: 6 }))]
+    fn rejects(input: &str) -> (usize, usize, String, ParseErrorKind) {
+        error(input)
+    }
+
+    #[test]
+    fn immediates_are_not_registers() {
+        assert!("seti 7 8 0\naddi 1 99 2".parse::<Cpu>().is_ok());
+        assert!(Cpu::parse("addr 3 3 3", 4, Dialect::Puzzle).is_ok());
+        assert!(Cpu::parse("addr 4 3 3", 4, Dialect::Puzzle).is_err());
+    }
+
+    #[test]
+    fn dialect() {
+        assert_eq!(Dialect::default(), Dialect::Puzzle);
+        assert!("halt 0 0 0".parse::<Instruction>().is_err());
+        let source = "#ip 5\nseti 8 0 0\ndivi 0 2 0";
+        let e = Cpu::parse(source, 6, Dialect::Puzzle).unwrap_err();
+        assert_eq!(
+            (e.line, e.column, e.token.as_str(), e.kind),
+            (3, 1, "divi", ParseErrorKind::Extension)
+        );
+        assert_eq!(
+            e.to_string(),
+            "line 3, column 1: \"divi\" is an extension, not a puzzle op"
+        );
+        let mut cpu = Cpu::parse(source, 6, Dialect::Extended).unwrap();
+        cpu.run();
+        assert_eq!(cpu.registers[0], 4);
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let ops = Op::iter().collect::<Vec<_>>();
+        let rng = &mut Rng::new(19);
+        let mut operand = || rng.next_u64() as Number >> rng.below(64);
+        let instructions = (0..500)
+            .map(|_| Instruction::new(ops[operand() % ops.len()], operand(), operand(), operand()))
+            .collect::<Vec<_>>();
+        for instr in &instructions {
+            let text = instr.to_string();
+            assert_eq!(Instruction::parse(&text, Dialect::Extended), Ok(*instr));
+            let puzzle = text.parse::<Instruction>();
+            assert_eq!(puzzle.is_ok(), !instr.op.is_extension(), "{:?}", text);
+        }
+
+        for input in samples(19).chain(samples(21)) {
+            let cpu: Cpu = input.parse().unwrap();
+            let again: Cpu = cpu.to_string().parse().unwrap();
+            assert_eq!((again.pc_reg, again.program), (cpu.pc_reg, cpu.program));
+        }
+    }
+
+    #[test]
+    fn message() {
+        let e = "#ip 0\nseti 1 2 3 4".parse::<Cpu>().unwrap_err();
+        assert_eq!(e.to_string(), "line 2, column 12: unexpected \"4\"");
+    }
+
+    #[test]
+    fn unbound_ip() {
+        let mut cpu = Cpu::parse("seti 7 0 0\naddi 0 5 0\nseti 9 0 3", 4, Dialect::Puzzle).unwrap();
+        assert_eq!(cpu.pc_reg, None);
+        assert_eq!(cpu.to_string(), "seti 7 0 0\naddi 0 5 0\nseti 9 0 3\n");
+        let mut checked = cpu.clone();
+        cpu.run();
+        assert_eq!(cpu.registers, vec![12, 0, 0, 9]);
+        assert_eq!(checked.try_run(10), Ok(()));
+        assert_eq!(checked.registers, cpu.registers);
+        assert_eq!(checked.cycles, 3);
     }
 }
@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Context};
+use itertools::Itertools;
+
+use super::{
+    disasm::{disassemble, Flow},
+    Cpu, Number, Op,
+};
+
+#[derive(Debug)]
+enum Operand<'a> {
+    Value(Number),
+    Symbol(&'a str, i64),
+}
+
+struct Line<'a> {
+    number: usize,
+    op: Op,
+    operands: [Operand<'a>; 3],
+    jump: bool,
+}
+
+fn identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn operand(token: &str) -> anyhow::Result<Operand<'_>> {
+    if token == "_" {
+        return Ok(Operand::Value(0));
+    }
+    if let Ok(value) = token.parse() {
+        return Ok(Operand::Value(value));
+    }
+    let (name, offset) = match token.find(['+', '-']) {
+        Some(at) => {
+            let offset: i64 = token[at + 1..]
+                .parse()
+                .with_context(|| format!("bad offset in {:?}", token))?;
+            let offset = if &token[at..=at] == "-" {
+                -offset
+            } else {
+                offset
+            };
+            (&token[..at], offset)
+        }
+        None => (token, 0),
+    };
+    if !identifier(name) {
+        bail!("bad operand {:?}", token);
+    }
+    Ok(Operand::Symbol(name, offset))
+}
+
+fn define<'a>(
+    symbols: &mut HashMap<&'a str, Number>,
+    name: &'a str,
+    value: Number,
+) -> anyhow::Result<()> {
+    if !identifier(name) {
+        bail!("{:?} is not a valid name", name);
+    }
+    if symbols.insert(name, value).is_some() {
+        bail!("{:?} is already defined", name);
+    }
+    Ok(())
+}
+
+fn resolve(symbols: &HashMap<&str, Number>, operand: &Operand) -> anyhow::Result<Number> {
+    match *operand {
+        Operand::Value(value) => Ok(value),
+        Operand::Symbol(name, offset) => {
+            let value = symbols
+                .get(name)
+                .copied()
+                .or_else(|| name.strip_prefix('r').and_then(|n| n.parse().ok()))
+                .ok_or_else(|| anyhow!("undefined symbol {:?}", name))?;
+            // Offsets are applied in i64, so only a bare symbol can name values past i64::MAX.
+            if offset == 0 {
+                return Ok(value);
+            }
+            let value = i64::try_from(value)
+                .ok()
+                .and_then(|value| value.checked_add(offset))
+                .ok_or_else(|| anyhow!("constant {:?}{:+} is out of range", name, offset))?;
+            Number::try_from(value).map_err(|_| anyhow!("{:?}{:+} is negative", name, offset))
+        }
+    }
+}
+
+fn register(value: Number, registers: usize) -> anyhow::Result<Number> {
+    if value >= registers {
+        bail!("register {} is outside {} registers", value, registers);
+    }
+    Ok(value)
+}
+
+pub fn assemble(source: &str) -> anyhow::Result<String> {
+    let mut symbols: HashMap<&str, Number> = HashMap::new();
+    let mut registers = Cpu::default().registers.len();
+    let mut ip = None;
+    let mut lines = vec![];
+
+    for (number, raw) in source.lines().enumerate() {
+        let number = number + 1;
+        let mut text = raw.split(';').next().unwrap().trim();
+        while let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            if !identifier(label) {
+                bail!("line {}: bad label {:?}", number, label);
+            }
+            define(&mut symbols, label, lines.len()).with_context(|| format!("line {}", number))?;
+            text = rest.trim();
+        }
+
+        let words = text.split_ascii_whitespace().collect_vec();
+        let parsed = match words.as_slice() {
+            [] => continue,
+            ["#ip", reg] => {
+                ip = Some(operand(reg)?);
+                Ok(())
+            }
+            [".registers", count] => count
+                .parse()
+                .with_context(|| format!("bad register count {:?}", count))
+                .map(|count| registers = count),
+            [".reg", name, value] | [".const", name, value] => value
+                .parse()
+                .with_context(|| format!("bad value {:?}", value))
+                .and_then(|value| define(&mut symbols, name, value)),
+            ["jmp", target] => operand(target).map(|target| {
+                lines.push(Line {
+                    number,
+                    op: Op::Seti,
+                    operands: [target, Operand::Value(0), Operand::Symbol("ip", 0)],
+                    jump: true,
+                })
+            }),
+            [op] => match op.parse::<Op>() {
+                Ok(op) if !op.writes() => {
+                    lines.push(Line {
+                        number,
+                        op,
+                        operands: [Operand::Value(0), Operand::Value(0), Operand::Value(0)],
+                        jump: false,
+                    });
+                    Ok(())
+                }
+                _ => Err(anyhow!("cannot parse {:?}", text)),
+            },
+            [op, a, b, c] => op
+                .parse::<Op>()
+                .map_err(|_| anyhow!("unknown op {:?}", op))
+                .and_then(|op| {
+                    lines.push(Line {
+                        number,
+                        op,
+                        operands: [operand(a)?, operand(b)?, operand(c)?],
+                        jump: false,
+                    });
+                    Ok(())
+                }),
+            _ => Err(anyhow!("cannot parse {:?}", text)),
+        };
+        parsed.with_context(|| format!("line {}", number))?;
+    }
+
+    let pc_reg = match ip {
+        Some(ip) => Some(resolve(&symbols, &ip)?),
+        None => symbols.get("ip").copied(),
+    };
+    let pc_reg = pc_reg
+        .map(|r| register(r, registers))
+        .transpose()
+        .context("#ip")?;
+
+    let mut output = String::new();
+    if let Some(pc_reg) = pc_reg {
+        symbols.entry("ip").or_insert(pc_reg);
+        output.push_str(&format!("#ip {}\n", pc_reg));
+    }
+    for line in lines {
+        let (ra, rb) = line.op.reads();
+        let resolved = line
+            .operands
+            .iter()
+            .zip([ra, rb, line.op.writes()])
+            .enumerate()
+            .map(|(i, (o, is_register))| {
+                let value = resolve(&symbols, o)?;
+                if line.jump && i == 0 {
+                    // seti leaves the ip one short of where execution resumes.
+                    value.checked_sub(1).ok_or_else(|| {
+                        anyhow!("cannot jmp to pc 0: the ip would have to be set to -1")
+                    })
+                } else if is_register {
+                    register(value, registers)
+                } else {
+                    Ok(value)
+                }
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
+            .with_context(|| format!("line {}", line.number))?;
+        output.push_str(&format!(
+            "{} {} {} {}\n",
+            line.op, resolved[0], resolved[1], resolved[2]
+        ));
+    }
+    Ok(output)
+}
+
+pub fn listing(cpu: &Cpu) -> String {
+    let disassembly = disassemble(cpu);
+    let mut output = match cpu.pc_reg {
+        Some(pc_reg) => format!(".reg ip {}\n#ip ip\n", pc_reg),
+        None => String::new(),
+    };
+    let register = |r: Number| {
+        if cpu.is_ip(r) {
+            "ip".to_string()
+        } else {
+            format!("r{}", r)
+        }
+    };
+    for statement in &disassembly.statements {
+        if disassembly.labels.contains(&statement.pc) {
+            output.push_str(&format!("L{}:\n", statement.pc));
+        }
+        let instr = statement.instr;
+        let (ra, rb) = instr.op.reads();
+        let a = match statement.flow {
+            Flow::Goto(target) if instr.op == Op::Seti => format!("L{}-1", target),
+            _ if ra => register(instr.a),
+            _ => instr.a.to_string(),
+        };
+        let b = if rb {
+            register(instr.b)
+        } else {
+            instr.b.to_string()
+        };
+        output.push_str(&format!(
+            "    {} {} {} {}\n",
+            instr.op,
+            a,
+            b,
+            register(instr.c)
+        ));
+    }
+    output
+}
+
+#[cfg(test)]
+mod assemble {
+    use super::*;
+    use crate::wasm::Dialect;
+
+    #[test]
+    fn example() {
+        let source = "\
+; counts r1 up to LIMIT, then halts
+#ip ip
+.reg ip 2
+.reg acc 1
+.reg flag 3
+.const LIMIT 10
+
+        seti 0 _ acc
+loop:   addi acc 1 acc
+        eqri acc LIMIT flag   ; finished?
+        addr flag ip ip
+        jmp loop
+        seti 99 _ ip
+";
+        let text = assemble(source).unwrap();
+        assert_eq!(
+            text,
+            "#ip 2
+seti 0 0 1
+addi 1 1 1
+eqri 1 10 3
+addr 3 2 2
+seti 0 0 2
+seti 99 0 2
+"
+        );
+        let mut cpu: Cpu = text.trim_end().parse().unwrap();
+        cpu.run();
+        assert_eq!(cpu.registers[1], 10);
+    }
+
+    #[test]
+    fn extensions() {
+        let text = assemble(".reg n 1\n    divi n 2 n\n    halt\n    nop").unwrap();
+        assert_eq!(text, "divi 1 2 1\nhalt 0 0 0\nnop 0 0 0\n");
+        let mut cpu = Cpu::parse(&text, 6, Dialect::Extended).unwrap();
+        cpu.registers[1] = 9;
+        cpu.run();
+        assert_eq!((cpu.registers[1], cpu.cycles), (4, 2));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(assemble("seti nowhere 0 0").is_err());
+        assert!(assemble("foo 1 2 3").is_err());
+        assert!(assemble("a: a: seti 1 0 0").is_err());
+        assert!(assemble("seti 1 0").is_err());
+        assert!(assemble("seti").is_err());
+    }
+
+    #[test_case("start: seti 1 0 0\njmp start" => "line 2: cannot jmp to pc 0: the ip would have to be set to -1")]
+    #[test_case("seti 1 0 0\njmp 0" => "line 2: cannot jmp to pc 0: the ip would have to be set to -1")]
+    #[test_case("addr 1 6 0" => "line 1: register 6 is outside 6 registers")]
+    #[test_case(".registers 4\nseti 7 0 4" => "line 2: register 4 is outside 4 registers")]
+    #[test_case(".registers 4\n#ip 4" => "#ip: register 4 is outside 4 registers")]
+    #[test_case(".reg 2x 1" => "line 1: \"2x\" is not a valid name")]
+    #[test_case(".const a-b 1" => "line 1: \"a-b\" is not a valid name")]
+    #[test_case("1a: seti 1 0 0" => "line 1: bad label \"1a\"")]
+    #[test_case(".const BIG 18446744073709551615\nseti BIG+1 0 0" => "line 2: constant \"BIG\"+1 is out of range")]
+    #[test_case(".const BIG 9223372036854775807\nseti BIG+1 0 0" => "line 2: constant \"BIG\"+1 is out of range")]
+    #[test_case(".const LOW 1\nseti LOW-2 0 0" => "line 2: \"LOW\"-2 is negative")]
+    fn rejects(source: &str) -> String {
+        format!("{:#}", assemble(source).unwrap_err())
+    }
+
+    #[test]
+    fn large_constants() {
+        let source = ".const BIG 18446744073709551615\n.const MAX 9223372036854775806\nseti BIG 0 0\nseti MAX+1 0 0";
+        assert_eq!(
+            assemble(source).unwrap(),
+            "seti 18446744073709551615 0 0\nseti 9223372036854775807 0 0\n"
+        );
+    }
+
+    #[test]
+    fn jumps() {
+        let source = "#ip 5\nseti 1 0 0\nhere: jmp here\njmp 1";
+        assert_eq!(
+            assemble(source).unwrap(),
+            "#ip 5\nseti 1 0 0\nseti 0 0 5\nseti 0 0 5\n"
+        );
+        let text = assemble(".registers 4\n#ip 3\nseti 1 0 2").unwrap();
+        assert!(Cpu::parse(&text, 4, Dialect::Puzzle).is_ok());
+    }
+
+    #[test]
+    fn round_trip() {
+        let text = include_str!("../day19_example.txt");
+        let cpu: Cpu = text.parse().unwrap();
+        assert_eq!(
+            assemble(&listing(&cpu)).unwrap().trim_end(),
+            text.trim_end()
+        );
+        assert_eq!(cpu.to_string().trim_end(), text.trim_end());
+    }
+}
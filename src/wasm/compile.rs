@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+
+use super::{
+    optimize::{analyse, Shortcut},
+    Cpu, CpuError, Instruction, Number, Op,
+};
+
+type Step = Box<dyn Fn(&mut [Number]) -> Result<(), CpuError>>;
+
+struct Block {
+    steps: Vec<Step>,
+    cycles: usize,
+    exit: Option<Number>,
+}
+
+#[derive(Clone, Copy)]
+enum Src {
+    Register(usize),
+    Constant(Number),
+}
+
+fn binary(
+    a: Src,
+    b: Src,
+    c: usize,
+    f: impl Fn(Number, Number) -> Result<Number, CpuError> + 'static,
+) -> Step {
+    use Src::*;
+    match (a, b) {
+        (Register(a), Register(b)) => Box::new(move |r| {
+            r[c] = f(r[a], r[b])?;
+            Ok(())
+        }),
+        (Register(a), Constant(b)) => Box::new(move |r| {
+            r[c] = f(r[a], b)?;
+            Ok(())
+        }),
+        (Constant(a), Register(b)) => Box::new(move |r| {
+            r[c] = f(a, r[b])?;
+            Ok(())
+        }),
+        // Fold, unless it faults: that has to wait until the step runs.
+        (Constant(a), Constant(b)) => match f(a, b) {
+            Ok(value) => Box::new(move |r| {
+                r[c] = value;
+                Ok(())
+            }),
+            Err(fault) => Box::new(move |_| Err(fault)),
+        },
+    }
+}
+
+fn decode(pc: Number, cpu: &Cpu, instr: &Instruction) -> Step {
+    let pc_reg = cpu.pc_reg;
+    if let Some(register) = instr.invalid_register(pc_reg, cpu.registers.len()) {
+        let fault = CpuError::InvalidRegister { pc, register };
+        return Box::new(move |_| Err(fault));
+    }
+    let (ra, rb) = instr.op.reads();
+    let src = |value: Number, register: bool| {
+        if !register {
+            Src::Constant(value)
+        } else if pc_reg == Some(value) {
+            Src::Constant(pc)
+        } else {
+            Src::Register(value)
+        }
+    };
+    let (op, arithmetic, instr) = (instr.op, cpu.arithmetic, *instr);
+    let evaluate = move |a, b| {
+        op.evaluate(a, b, arithmetic).ok_or(
+            if matches!(op, Op::Divr | Op::Divi | Op::Modr | Op::Modi) && b == 0 {
+                CpuError::DivisionByZero { pc, instr }
+            } else {
+                CpuError::Overflow { pc, instr }
+            },
+        )
+    };
+    match (op, src(instr.a, ra)) {
+        (Op::Nop | Op::Halt, _) => Box::new(|_| Ok(())),
+        (Op::Setr | Op::Seti, Src::Register(a)) => {
+            let c = instr.c;
+            Box::new(move |r| {
+                r[c] = r[a];
+                Ok(())
+            })
+        }
+        (Op::Setr | Op::Seti, Src::Constant(a)) => {
+            let c = instr.c;
+            Box::new(move |r| {
+                r[c] = a;
+                Ok(())
+            })
+        }
+        (_, a) => binary(a, src(instr.b, rb), instr.c, evaluate),
+    }
+}
+
+pub struct Compiled {
+    pc_reg: Option<usize>,
+    blocks: Vec<Block>,
+}
+
+impl Compiled {
+    pub fn new(cpu: &Cpu, breaks: &[Number]) -> Self {
+        let pc_reg = cpu.pc_reg;
+        let shortcuts: HashMap<Number, Shortcut> = analyse(cpu)
+            .into_iter()
+            .filter(|s| !breaks.iter().any(|b| (s.start..s.exit).contains(b)))
+            .map(|s| (s.start, s))
+            .collect();
+
+        let blocks = (0..cpu.program.len())
+            .map(|start| {
+                if let (Some(&shortcut), Some(ip)) = (shortcuts.get(&start), pc_reg) {
+                    let (exit, arithmetic) = (shortcut.exit, cpu.arithmetic);
+                    let program = cpu.program.clone();
+                    return Block {
+                        steps: vec![Box::new(move |r: &mut [Number]| {
+                            shortcut.apply(r, arithmetic, &program)?;
+                            r[ip] = exit - 1;
+                            Ok(())
+                        })],
+                        cycles: 1,
+                        exit: None,
+                    };
+                }
+
+                let mut steps = vec![];
+                let mut pc = start;
+                let mut halts = false;
+                loop {
+                    let instr = &cpu.program[pc];
+                    steps.push(decode(pc, cpu, instr));
+                    if instr.op == Op::Halt {
+                        if let Some(ip) = pc_reg {
+                            let last = pc;
+                            steps.push(Box::new(move |r: &mut [Number]| {
+                                r[ip] = last;
+                                Ok(())
+                            }));
+                        }
+                        halts = true;
+                        break;
+                    }
+                    if instr.op.writes() && pc_reg == Some(instr.c) {
+                        break;
+                    }
+                    if pc + 1 == cpu.program.len() || breaks.contains(&(pc + 1)) {
+                        if let Some(ip) = pc_reg {
+                            let last = pc;
+                            steps.push(Box::new(move |r: &mut [Number]| {
+                                r[ip] = last;
+                                Ok(())
+                            }));
+                        }
+                        break;
+                    }
+                    pc += 1;
+                }
+                Block {
+                    steps,
+                    cycles: pc - start + 1,
+                    exit: if halts {
+                        Some(cpu.program.len())
+                    } else {
+                        pc_reg.is_none().then_some(pc + 1)
+                    },
+                }
+            })
+            .collect();
+
+        Self { pc_reg, blocks }
+    }
+
+    fn block(&self, cpu: &mut Cpu) {
+        let start = cpu.pc as usize;
+        let block = &self.blocks[start];
+        // Steps line up with instructions, so a fault leaves the cpu where
+        // the interpreter would: on the faulting instruction. That binds the
+        // ip only once it knows the ip and the written register are valid.
+        for (i, step) in block.steps.iter().enumerate() {
+            if let Err(fault) = step(&mut cpu.registers) {
+                let pc = start + i;
+                let bound = !matches!(fault, CpuError::InvalidRegister { register, .. }
+                    if register == cpu.program[pc].c || Some(register) == self.pc_reg);
+                if let Some(ip) = self.pc_reg.filter(|&ip| ip < cpu.registers.len()) {
+                    if bound {
+                        cpu.registers[ip] = pc;
+                    } else if i > 0 {
+                        cpu.registers[ip] = pc - 1;
+                    }
+                }
+                cpu.pc = pc as i32;
+                cpu.cycles += i;
+                cpu.fault = Some(fault);
+                return;
+            }
+        }
+        cpu.cycles += block.cycles;
+        let last = match (block.exit, self.pc_reg) {
+            (Some(exit), _) => exit - 1,
+            (None, Some(ip)) => cpu.registers[ip],
+            (None, None) => unreachable!("blocks without an ip register have a static exit"),
+        };
+        cpu.pc = i32::try_from(last)
+            .ok()
+            .and_then(|pc| pc.checked_add(1))
+            .unwrap_or(i32::MAX);
+    }
+
+    pub fn run(&self, cpu: &mut Cpu) {
+        while !cpu.halted() {
+            self.block(cpu);
+        }
+    }
+
+    pub fn run_till_pc(&self, cpu: &mut Cpu, target: usize) {
+        while !cpu.halted() {
+            self.block(cpu);
+            if cpu.pc == target as i32 {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod compiled {
+    use super::*;
+    use crate::wasm::Dialect;
+
+    #[test_case(include_str!("../day19_example.txt"))]
+    #[test_case(include_str!("divisor_sum.txt"))]
+    #[test_case("seti 7 0 1\naddr 0 1 2\nmuli 2 3 3")]
+    fn matches_interpreter(source: &str) {
+        let mut cpu: Cpu = source.parse().unwrap();
+        let mut expected = cpu.clone();
+        Compiled::new(&cpu, &[]).run(&mut cpu);
+        expected.run();
+        assert_eq!(cpu.registers, expected.registers);
+        assert!(cpu.halted());
+    }
+
+    #[test_case(23, 1)]
+    #[test_case(12, 2)]
+    fn stops_at_breaks(pc: usize, register: usize) {
+        let source = include_str!("hash.txt");
+        let mut interpreted: Cpu = source.parse().unwrap();
+        let mut cpu = interpreted.clone();
+        let compiled = Compiled::new(&cpu, &[pc]);
+        for _ in 0..20 {
+            interpreted.run_till_pc(pc);
+            compiled.run_till_pc(&mut cpu, pc);
+            assert_eq!(cpu.pc, interpreted.pc);
+            assert_eq!(cpu.registers[register], interpreted.registers[register]);
+        }
+    }
+
+    #[test_case("#ip 5\nseti 3 0 0\ndivi 0 0 1\nseti 4 0 0")]
+    #[test_case("#ip 5\nseti 3 0 0\ndivi 5 0 1\nseti 4 0 0")]
+    #[test_case("#ip 5\nseti 3 0 0\nshl 5 64 1\nseti 4 0 0")]
+    #[test_case("#ip 5\nseti 3 0 0\nmuli 0 999 0\nmulr 0 0 0\nmulr 0 0 0\nmulr 0 0 0\nseti 4 0 0")]
+    fn faults_like_the_interpreter(source: &str) {
+        let mut cpu = Cpu::parse(source, 6, Dialect::Extended).unwrap();
+        let mut expected = cpu.clone();
+        Compiled::new(&cpu, &[]).run(&mut cpu);
+        expected.run();
+        assert!(cpu.fault.is_some());
+        assert_eq!(cpu.fault, expected.fault);
+        assert_eq!(cpu.registers, expected.registers);
+        assert_eq!((cpu.pc, cpu.cycles), (expected.pc, expected.cycles));
+    }
+
+    #[test_case(None, Instruction::new(Op::Addr, 0, 9, 1))]
+    #[test_case(None, Instruction::new(Op::Seti, 0, 9, 7))]
+    #[test_case(None, Instruction::new(Op::Halt, 0, 0, 6))]
+    #[test_case(Some(7), Instruction::new(Op::Seti, 4, 0, 0))]
+    fn invalid_registers(pc_reg: Option<usize>, instr: Instruction) {
+        let mut cpu: Cpu = "#ip 5\nseti 3 0 0\naddi 0 1 0".parse().unwrap();
+        cpu.program.insert(1, instr);
+        if pc_reg.is_some() {
+            cpu.pc_reg = pc_reg;
+        }
+        let mut expected = cpu.clone();
+        Compiled::new(&cpu, &[]).run(&mut cpu);
+        expected.run();
+        assert!(matches!(
+            expected.fault,
+            Some(CpuError::InvalidRegister { .. })
+        ));
+        assert_eq!(cpu.fault, expected.fault);
+        assert_eq!(cpu.registers, expected.registers);
+        assert_eq!((cpu.pc, cpu.cycles), (expected.pc, expected.cycles));
+    }
+
+    #[test]
+    fn straight_line() {
+        let mut cpu: Cpu = "#ip 4
+seti 7 0 1
+addr 4 1 2
+muli 2 3 3
+gtir 20 3 0
+eqrr 0 0 5"
+            .parse()
+            .unwrap();
+        let mut expected = cpu.clone();
+        Compiled::new(&cpu, &[]).run(&mut cpu);
+        expected.run();
+        assert_eq!(cpu.registers, expected.registers);
+        assert_eq!(cpu.cycles, expected.cycles);
+    }
+}
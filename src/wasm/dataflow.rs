@@ -0,0 +1,160 @@
+use super::{
+    disasm::{disassemble, Disassembly, Flow},
+    Cpu, Number, Op,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Register(usize),
+    Immediate(Number),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HaltCondition {
+    pub pc: Number,
+    pub compared: Operand,
+    pub when_equal: bool,
+}
+
+pub fn readers(cpu: &Cpu, register: usize) -> Vec<Number> {
+    cpu.program
+        .iter()
+        .enumerate()
+        .filter(|(_, instr)| {
+            let (ra, rb) = instr.op.reads();
+            (ra && instr.a == register) || (rb && instr.b == register)
+        })
+        .map(|(pc, _)| pc)
+        .collect()
+}
+
+fn halts_from(disassembly: &Disassembly, mut pc: Number) -> bool {
+    for _ in 0..=disassembly.statements.len() {
+        let Some(statement) = disassembly.statements.get(pc) else {
+            return true;
+        };
+        pc = match statement.flow {
+            Flow::Next => pc + 1,
+            Flow::Goto(target) => target,
+            Flow::Halt => return true,
+            Flow::Branch(_) | Flow::Indirect => return false,
+        };
+    }
+    false
+}
+
+fn compared(cpu: &Cpu, pc: Number, register: usize) -> Option<Operand> {
+    use Op::*;
+    let instr = &cpu.program[pc];
+    match instr.op {
+        Eqrr if instr.a == register && instr.b != register => Some(Operand::Register(instr.b)),
+        Eqrr if instr.b == register && instr.a != register => Some(Operand::Register(instr.a)),
+        Eqri if instr.a == register => Some(Operand::Immediate(instr.b)),
+        Eqir if instr.b == register => Some(Operand::Immediate(instr.a)),
+        _ => None,
+    }
+}
+
+pub fn halt_conditions(cpu: &Cpu, register: usize) -> Vec<HaltCondition> {
+    let disassembly = disassemble(cpu);
+    readers(cpu, register)
+        .into_iter()
+        .filter_map(|pc| {
+            let compared = compared(cpu, pc, register)?;
+            let flag = cpu.program[pc].c;
+            let branch = disassembly.statements.get(pc + 1)?;
+            let Flow::Branch(target) = branch.flow else {
+                return None;
+            };
+            let (ra, rb) = branch.instr.op.reads();
+            let tested = (ra && branch.instr.a == flag) || (rb && branch.instr.b == flag);
+            if !tested {
+                return None;
+            }
+            match (
+                halts_from(&disassembly, target),
+                halts_from(&disassembly, pc + 2),
+            ) {
+                (true, false) => Some(HaltCondition {
+                    pc,
+                    compared,
+                    when_equal: true,
+                }),
+                (false, true) => Some(HaltCondition {
+                    pc,
+                    compared,
+                    when_equal: false,
+                }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+pub fn halting_value(cpu: &Cpu) -> Option<HaltCondition> {
+    match halt_conditions(cpu, 0).as_slice() {
+        [condition] if condition.when_equal => Some(*condition),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod halt_conditions {
+    use super::*;
+
+    #[test]
+    fn day21() {
+        let cpu: Cpu = include_str!("hash.txt").parse().unwrap();
+        assert_eq!(readers(&cpu, 0), vec![23]);
+        assert_eq!(
+            halting_value(&cpu),
+            Some(HaltCondition {
+                pc: 23,
+                compared: Operand::Register(1),
+                when_equal: true
+            })
+        );
+    }
+
+    #[test]
+    fn reordered() {
+        let cpu: Cpu = "#ip 4
+seti 5 0 2
+addi 2 3 2
+eqrr 0 2 1
+addr 4 1 4
+seti 0 0 4
+seti 99 0 3
+mulr 4 4 4"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            halting_value(&cpu),
+            Some(HaltCondition {
+                pc: 2,
+                compared: Operand::Register(2),
+                when_equal: true
+            })
+        );
+    }
+
+    #[test]
+    fn inverted() {
+        let cpu: Cpu = "#ip 4
+eqri 0 7 1
+addr 1 4 4
+mulr 4 4 4
+seti 0 0 4"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            halt_conditions(&cpu, 0),
+            vec![HaltCondition {
+                pc: 0,
+                compared: Operand::Immediate(7),
+                when_equal: false
+            }]
+        );
+        assert_eq!(halting_value(&cpu), None);
+    }
+}
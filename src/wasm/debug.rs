@@ -0,0 +1,261 @@
+use std::collections::{BTreeSet, VecDeque};
+
+use itertools::Itertools;
+
+use super::{Cpu, CpuError, Number};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watch {
+    Equals(usize, Number),
+    Changes(usize),
+}
+
+impl Watch {
+    fn triggered(&self, before: &[Number], after: &[Number]) -> bool {
+        match *self {
+            Watch::Equals(reg, value) => after[reg] == value && before[reg] != value,
+            Watch::Changes(reg) => after[reg] != before[reg],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Step,
+    Breakpoint(Number),
+    Watchpoint(Watch),
+    Halted,
+    Fault(CpuError),
+    Budget,
+}
+
+#[derive(Debug, Clone)]
+struct Frame {
+    pc: i32,
+    registers: Vec<Number>,
+    cycles: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Debugger {
+    pub cpu: Cpu,
+    breakpoints: BTreeSet<Number>,
+    watches: Vec<Watch>,
+    history: VecDeque<Frame>,
+    depth: usize,
+}
+
+impl Debugger {
+    pub fn new(cpu: Cpu, depth: usize) -> Self {
+        Self {
+            cpu,
+            breakpoints: BTreeSet::new(),
+            watches: vec![],
+            history: VecDeque::with_capacity(depth),
+            depth,
+        }
+    }
+
+    pub fn pc(&self) -> i32 {
+        self.cpu.pc
+    }
+
+    pub fn break_at(&mut self, pc: Number) {
+        self.breakpoints.insert(pc);
+    }
+
+    pub fn clear_break(&mut self, pc: Number) -> bool {
+        self.breakpoints.remove(&pc)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = &Number> {
+        self.breakpoints.iter()
+    }
+
+    pub fn watch(&mut self, watch: Watch) {
+        if !self.watches.contains(&watch) {
+            self.watches.push(watch);
+        }
+    }
+
+    pub fn unwatch(&mut self, watch: Watch) -> bool {
+        let before = self.watches.len();
+        self.watches.retain(|&w| w != watch);
+        before != self.watches.len()
+    }
+
+    pub fn watches(&self) -> impl Iterator<Item = &Watch> {
+        self.watches.iter()
+    }
+
+    pub fn history(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn step(&mut self) -> Stop {
+        if self.cpu.halted() {
+            return Stop::Halted;
+        }
+
+        if self.history.len() == self.depth {
+            self.history.pop_front();
+        }
+        if self.depth > 0 {
+            self.history.push_back(Frame {
+                pc: self.cpu.pc,
+                registers: self.cpu.registers.clone(),
+                cycles: self.cpu.cycles,
+            });
+        }
+
+        let before = self.cpu.registers.clone();
+        if let Err(fault) = self.cpu.try_apply() {
+            // Nothing ran, so there is nothing to step back over.
+            if self.depth > 0 {
+                self.history.pop_back();
+            }
+            return Stop::Fault(fault);
+        }
+
+        if let Some(&watch) = self
+            .watches
+            .iter()
+            .find(|w| w.triggered(&before, &self.cpu.registers))
+        {
+            return Stop::Watchpoint(watch);
+        }
+        if self.cpu.halted() {
+            return Stop::Halted;
+        }
+        let pc = self.cpu.pc as Number;
+        if self.breakpoints.contains(&pc) {
+            return Stop::Breakpoint(pc);
+        }
+        Stop::Step
+    }
+
+    pub fn resume(&mut self, budget: usize) -> Stop {
+        for _ in 0..budget {
+            match self.step() {
+                Stop::Step => continue,
+                stop => return stop,
+            }
+        }
+        Stop::Budget
+    }
+
+    pub fn back(&mut self) -> bool {
+        match self.history.pop_back() {
+            Some(frame) => {
+                self.cpu.pc = frame.pc;
+                self.cpu.registers = frame.registers;
+                self.cpu.cycles = frame.cycles;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn dump(&self) -> String {
+        format!(
+            "pc={} cycles={} [{}]",
+            self.cpu.pc,
+            self.cpu.cycles,
+            self.cpu
+                .registers
+                .iter()
+                .enumerate()
+                .map(|(i, v)| if self.cpu.is_ip(i) {
+                    format!("ip:{}", v)
+                } else {
+                    format!("r{}:{}", i, v)
+                })
+                .join(" ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod debugger {
+    use super::*;
+    use crate::wasm::{Instruction, Op};
+
+    fn example() -> Debugger {
+        Debugger::new(include_str!("../day19_example.txt").parse().unwrap(), 3)
+    }
+
+    #[test]
+    fn breakpoint() {
+        let mut debugger = example();
+        debugger.break_at(4);
+        assert_eq!(debugger.resume(1000), Stop::Breakpoint(4));
+        assert_eq!(debugger.cpu.registers, vec![3, 5, 6, 0, 0, 0]);
+        assert_eq!(debugger.resume(1000), Stop::Halted);
+        assert_eq!(debugger.cpu.registers, vec![6, 5, 6, 0, 0, 9]);
+    }
+
+    #[test]
+    fn watchpoints() {
+        let mut debugger = example();
+        debugger.watch(Watch::Equals(2, 6));
+        assert_eq!(debugger.resume(1000), Stop::Watchpoint(Watch::Equals(2, 6)));
+        assert_eq!(debugger.cpu.cycles, 2);
+        debugger.watch(Watch::Changes(5));
+        assert_eq!(debugger.resume(1000), Stop::Watchpoint(Watch::Changes(5)));
+        assert_eq!(debugger.cpu.registers[5], 9);
+        assert_eq!(debugger.resume(1000), Stop::Halted);
+    }
+
+    #[test]
+    fn fault() {
+        let cpu = "#ip 5\nseti 2 0 0\nmuli 0 18446744073709551615 1"
+            .parse()
+            .unwrap();
+        let mut debugger = Debugger::new(cpu, 3);
+        let fault = CpuError::Overflow {
+            pc: 1,
+            instr: Instruction::new(Op::Muli, 0, usize::MAX, 1),
+        };
+        assert_eq!(debugger.resume(1000), Stop::Fault(fault));
+        assert_eq!((debugger.pc(), debugger.history()), (1, 1));
+        assert_eq!(debugger.step(), Stop::Fault(fault));
+        debugger.cpu.program[1].b = 1;
+        assert_eq!(debugger.step(), Stop::Halted);
+        assert_eq!(debugger.cpu.registers[1], 2);
+    }
+
+    #[test]
+    fn budget() {
+        let cpu: Cpu = "#ip 0\nseti 0 0 1\nseti 0 0 0".parse().unwrap();
+        let mut debugger = Debugger::new(cpu, 3);
+        assert_eq!(debugger.resume(1000), Stop::Budget);
+        assert_eq!(debugger.cpu.cycles, 1000);
+    }
+
+    #[test]
+    fn step_back() {
+        let mut debugger = example();
+        for _ in 0..3 {
+            debugger.step();
+        }
+        let after = debugger.cpu.registers.clone();
+        assert_eq!(debugger.step(), Stop::Step);
+        assert!(debugger.back());
+        assert_eq!(debugger.cpu.registers, after);
+        assert_eq!(debugger.pc(), 4);
+        assert!(debugger.back());
+        assert!(debugger.back());
+        assert!(!debugger.back());
+        assert_eq!(debugger.cpu.cycles, 1);
+    }
+
+    #[test]
+    fn dump() {
+        let mut debugger = example();
+        debugger.step();
+        assert_eq!(
+            debugger.dump(),
+            "pc=1 cycles=1 [ip:0 r1:5 r2:0 r3:0 r4:0 r5:0]"
+        );
+    }
+}
@@ -0,0 +1,278 @@
+// A flat listing, one line of pseudo-code per instruction: jumps through
+// the ip become gotos and labels, conditional skips become `if ... goto`,
+// and backward jump targets are marked `; loop`. Nothing is nested into
+// loop or if blocks.
+
+use std::collections::BTreeSet;
+
+use super::{Arithmetic, Cpu, Instruction, Number, Op};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Next,
+    Goto(Number),
+    Branch(Number),
+    Indirect,
+    Halt,
+}
+
+#[derive(Debug, Clone)]
+pub struct Statement {
+    pub pc: Number,
+    pub instr: Instruction,
+    pub flow: Flow,
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Disassembly {
+    pub pc_reg: Option<usize>,
+    pub statements: Vec<Statement>,
+    pub labels: BTreeSet<Number>,
+    pub loops: BTreeSet<Number>,
+}
+
+fn is_comparison(op: Op) -> bool {
+    use Op::*;
+    matches!(op, Gtir | Gtri | Gtrr | Eqir | Eqri | Eqrr)
+}
+
+struct Decoder<'a> {
+    cpu: &'a Cpu,
+}
+
+impl Decoder<'_> {
+    fn operand(&self, pc: Number, value: Number, register: bool) -> String {
+        if !register {
+            value.to_string()
+        } else if self.cpu.is_ip(value) {
+            pc.to_string()
+        } else {
+            format!("r{}", value)
+        }
+    }
+
+    fn expression(&self, pc: Number, instr: &Instruction) -> String {
+        use Op::*;
+        let (ra, rb) = instr.op.reads();
+        let a = self.operand(pc, instr.a, ra);
+        let b = self.operand(pc, instr.b, rb);
+        match instr.op {
+            Addr | Addi => format!("{} + {}", a, b),
+            Mulr | Muli => format!("{} * {}", a, b),
+            Banr | Bani => format!("{} & {}", a, b),
+            Borr | Bori => format!("{} | {}", a, b),
+            Setr | Seti => a,
+            Gtir | Gtri | Gtrr => format!("{} > {}", a, b),
+            Eqir | Eqri | Eqrr => format!("{} == {}", a, b),
+            Divr | Divi => format!("{} / {}", a, b),
+            Modr | Modi => format!("{} % {}", a, b),
+            Shl => format!("{} << {}", a, b),
+            Shr => format!("{} >> {}", a, b),
+            Nop | Halt => instr.op.to_string(),
+        }
+    }
+
+    fn target(&self, target: Number) -> String {
+        if target >= self.cpu.program.len() {
+            "halt".to_string()
+        } else {
+            format!("goto L{}", target)
+        }
+    }
+
+    fn constant(&self, pc: Number, instr: &Instruction) -> Option<Number> {
+        let (ra, rb) = instr.op.reads();
+        let pc_reg = self.cpu.pc_reg?;
+        if (ra && instr.a != pc_reg) || (rb && instr.b != pc_reg) {
+            return None;
+        }
+        let a = if ra { pc } else { instr.a };
+        let b = if rb { pc } else { instr.b };
+        instr.op.evaluate(a, b, Arithmetic::Wrapping)
+    }
+
+    fn condition(&self, pc: Number, instr: &Instruction) -> Option<String> {
+        let pc_reg = self.cpu.pc_reg?;
+        if instr.op != Op::Addr || pc == 0 {
+            return None;
+        }
+        let flag = match (instr.a == pc_reg, instr.b == pc_reg) {
+            (true, false) => instr.b,
+            (false, true) => instr.a,
+            _ => return None,
+        };
+        let prev = &self.cpu.program[pc - 1];
+        if is_comparison(prev.op) && prev.c == flag && prev.a != flag && prev.b != flag {
+            Some(self.expression(pc - 1, prev))
+        } else {
+            None
+        }
+    }
+
+    fn decode(&self, pc: Number, instr: &Instruction) -> Statement {
+        let (flow, text) = if instr.op == Op::Halt {
+            (Flow::Halt, "halt".to_string())
+        } else if !instr.op.writes() {
+            (Flow::Next, instr.op.to_string())
+        } else if !self.cpu.is_ip(instr.c) {
+            (
+                Flow::Next,
+                format!("r{} = {}", instr.c, self.expression(pc, instr)),
+            )
+        } else if let Some(value) = self.constant(pc, instr) {
+            match value.checked_add(1) {
+                Some(target) if target < self.cpu.program.len() => {
+                    (Flow::Goto(target), self.target(target))
+                }
+                _ => (Flow::Halt, "halt".to_string()),
+            }
+        } else if let Some(condition) = self.condition(pc, instr) {
+            (
+                Flow::Branch(pc + 2),
+                format!("if {} {}", condition, self.target(pc + 2)),
+            )
+        } else {
+            (
+                Flow::Indirect,
+                format!("goto 1 + {}", self.expression(pc, instr)),
+            )
+        };
+        Statement {
+            pc,
+            instr: *instr,
+            flow,
+            text,
+        }
+    }
+}
+
+pub fn disassemble(cpu: &Cpu) -> Disassembly {
+    let decoder = Decoder { cpu };
+    let statements: Vec<Statement> = cpu
+        .program
+        .iter()
+        .enumerate()
+        .map(|(pc, instr)| decoder.decode(pc, instr))
+        .collect();
+
+    let mut labels = BTreeSet::new();
+    let mut loops = BTreeSet::new();
+    for statement in &statements {
+        if let Flow::Goto(target) | Flow::Branch(target) = statement.flow {
+            if target < statements.len() {
+                labels.insert(target);
+                if target <= statement.pc {
+                    loops.insert(target);
+                }
+            }
+        }
+    }
+
+    Disassembly {
+        pc_reg: cpu.pc_reg,
+        statements,
+        labels,
+        loops,
+    }
+}
+
+impl Disassembly {
+    pub fn line(&self, pc: Number) -> String {
+        let statement = &self.statements[pc];
+        let label = if self.labels.contains(&pc) {
+            format!("L{}:", pc)
+        } else {
+            String::new()
+        };
+        let mut line = format!("{:<5}{:>3}: {}", label, pc, statement.text);
+        if self.loops.contains(&pc) {
+            line.push_str("  ; loop");
+        }
+        line
+    }
+}
+
+impl std::fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(pc_reg) = self.pc_reg {
+            writeln!(f, "#ip {}", pc_reg)?;
+        }
+        for pc in 0..self.statements.len() {
+            writeln!(f, "{}", self.line(pc))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod disassemble {
+    use super::*;
+    use crate::wasm::Dialect;
+
+    #[test]
+    fn example() {
+        let cpu: Cpu = include_str!("../day19_example.txt").parse().unwrap();
+        assert_eq!(
+            disassemble(&cpu).to_string(),
+            "#ip 0
+       0: r1 = 5
+       1: r2 = 6
+       2: goto L4
+       3: r3 = r1 + r2
+L4:    4: goto 1 + r1
+       5: r4 = 8
+       6: r5 = 9
+"
+        );
+    }
+
+    #[test]
+    fn branches_and_loops() {
+        let cpu: Cpu = "#ip 2
+seti 0 0 1
+addi 1 1 1
+addr 1 2 3
+eqrr 1 0 3
+addr 3 2 2
+seti 0 0 2
+mulr 2 2 2"
+            .parse()
+            .unwrap();
+        let disassembly = disassemble(&cpu);
+        assert_eq!(
+            disassembly.to_string(),
+            "#ip 2
+       0: r1 = 0
+L1:    1: r1 = r1 + 1  ; loop
+       2: r3 = r1 + 2
+       3: r3 = r1 == r0
+       4: if r1 == r0 goto L6
+       5: goto L1
+L6:    6: halt
+"
+        );
+        assert_eq!(disassembly.statements[4].flow, Flow::Branch(6));
+        assert_eq!(disassembly.statements[6].flow, Flow::Halt);
+    }
+
+    #[test]
+    fn jump_past_the_end() {
+        let cpu: Cpu = "#ip 5\nseti 18446744073709551615 0 5".parse().unwrap();
+        let disassembly = disassemble(&cpu);
+        assert_eq!(disassembly.statements[0].flow, Flow::Halt);
+        assert_eq!(disassembly.to_string(), "#ip 5\n       0: halt\n");
+    }
+
+    #[test]
+    fn unbound_ip() {
+        let cpu = Cpu::parse("seti 7 0 0\naddr 0 3 0\nmulr 0 0 2", 4, Dialect::Puzzle).unwrap();
+        assert_eq!(
+            disassemble(&cpu).to_string(),
+            "       0: r0 = 7
+       1: r0 = r0 + r3
+       2: r2 = r0 * r0
+"
+        );
+    }
+}
@@ -0,0 +1,526 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+
+use super::{Arithmetic, Cpu, CpuError, Instruction, Number, Op};
+
+const DIVISOR_SUM: &str = "
+seti 1 _ I
+seti 1 _ J
+mulr I J T
+eqrr T N T
+addr T ip ip
+addi ip 1 ip
+addr I ACC ACC
+addi J 1 J
+gtrr J N T
+addr ip T ip
+seti @2 _ ip
+addi I 1 I
+gtrr I N T
+addr T ip ip
+seti @1 _ ip";
+
+const DIVIDE: &str = "
+seti 0 _ Q
+addi Q 1 T
+muli T #K T
+gtrr T X T
+addr T ip ip
+addi ip 1 ip
+seti @9 _ ip
+addi Q 1 Q
+seti @1 _ ip";
+
+const MULTIPLY_ACCUMULATE: &str = "
+addr ACC A ACC
+addi CNT 1 CNT
+gtrr CNT N T
+addr T ip ip
+seti @0 _ ip";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Idiom {
+    DivisorSum {
+        n: usize,
+        acc: usize,
+        i: usize,
+        j: usize,
+        t: usize,
+    },
+    Divide {
+        dividend: usize,
+        divisor: Number,
+        quotient: usize,
+        t: usize,
+    },
+    MultiplyAccumulate {
+        acc: usize,
+        addend: usize,
+        counter: usize,
+        limit: usize,
+        t: usize,
+    },
+}
+
+impl Idiom {
+    // Does the arithmetic of the whole loop under the cpu's policy. An
+    // overflow gives the offset, within the idiom, of the instruction the
+    // interpreter would have faulted on.
+    pub fn apply(&self, r: &mut [Number], arithmetic: Arithmetic) -> Result<(), Number> {
+        match *self {
+            Idiom::DivisorSum { n, acc, i, j, t } => {
+                // Offsets of mulr I J T, addr I ACC ACC and addi J 1 J.
+                let (product_at, sum_at, next_at): (Number, Number, Number) = (2, 6, 7);
+                let n_value = r[n];
+                // The loop multiplies every pair of candidates, up to n * n.
+                arithmetic.mul(n_value, n_value).ok_or(product_at)?;
+                let mut sum = r[acc];
+                let mut d = 1;
+                while d <= n_value / d {
+                    if n_value.is_multiple_of(d) {
+                        sum = arithmetic.add(sum, d).ok_or(sum_at)?;
+                        if d != n_value / d {
+                            sum = arithmetic.add(sum, n_value / d).ok_or(sum_at)?;
+                        }
+                    }
+                    d += 1;
+                }
+                let next = arithmetic.add(n_value, 1).ok_or(next_at)?;
+                r[acc] = sum;
+                r[i] = next;
+                r[j] = next;
+                r[t] = 1;
+            }
+            Idiom::Divide {
+                dividend,
+                divisor,
+                quotient,
+                t,
+            } => {
+                // Offsets of addi Q 1 T and muli T #K T.
+                let (next_at, product_at): (Number, Number) = (1, 2);
+                let q = r[dividend] / divisor;
+                let next = arithmetic.add(q, 1).ok_or(next_at)?;
+                arithmetic.mul(next, divisor).ok_or(product_at)?;
+                r[quotient] = q;
+                r[t] = 1;
+            }
+            Idiom::MultiplyAccumulate {
+                acc,
+                addend,
+                counter,
+                limit,
+                t,
+            } => {
+                // Offsets of addr ACC A ACC and addi CNT 1 CNT.
+                let (sum_at, count_at): (Number, Number) = (0, 1);
+                let iterations = if r[counter] <= r[limit] {
+                    arithmetic.add(r[limit] - r[counter], 1).ok_or(count_at)?
+                } else {
+                    1
+                };
+                let product = arithmetic.mul(r[addend], iterations).ok_or(sum_at)?;
+                let sum = arithmetic.add(r[acc], product).ok_or(sum_at)?;
+                r[counter] = arithmetic.add(r[counter], iterations).ok_or(count_at)?;
+                r[acc] = sum;
+                r[t] = 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shortcut {
+    pub start: Number,
+    pub exit: Number,
+    pub idiom: Idiom,
+}
+
+impl Shortcut {
+    pub fn apply(
+        &self,
+        r: &mut [Number],
+        arithmetic: Arithmetic,
+        program: &[Instruction],
+    ) -> Result<(), CpuError> {
+        self.idiom.apply(r, arithmetic).map_err(|offset| {
+            let pc = self.start + offset;
+            CpuError::Overflow {
+                pc,
+                instr: program[pc],
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Arg {
+    Literal(Number),
+    Any,
+    Ip,
+    Jump(Number),
+    Register(&'static str),
+    Immediate(&'static str),
+}
+
+fn arg(token: &'static str) -> Arg {
+    match token {
+        "_" => Arg::Any,
+        "ip" => Arg::Ip,
+        _ if token.starts_with('#') => Arg::Immediate(token),
+        _ => match token.strip_prefix('@') {
+            Some(rel) => Arg::Jump(rel.parse().unwrap()),
+            None => match token.parse() {
+                Ok(value) => Arg::Literal(value),
+                Err(_) => Arg::Register(token),
+            },
+        },
+    }
+}
+
+fn pattern(text: &'static str) -> Vec<(Op, [Arg; 3])> {
+    text.lines()
+        .filter(|l| !l.is_empty())
+        .map(|l| {
+            let words = l.split_ascii_whitespace().collect_vec();
+            (
+                words[0].parse().unwrap(),
+                [arg(words[1]), arg(words[2]), arg(words[3])],
+            )
+        })
+        .collect()
+}
+
+type Bindings = HashMap<&'static str, Number>;
+
+struct Matcher<'a> {
+    program: &'a [Instruction],
+    pc_reg: Option<usize>,
+    start: Number,
+    pattern: Vec<(Op, [Arg; 3])>,
+}
+
+impl Matcher<'_> {
+    fn bind(&self, bindings: &mut Bindings, arg: Arg, value: Number) -> bool {
+        match arg {
+            Arg::Literal(literal) => literal == value,
+            Arg::Any => true,
+            Arg::Ip => self.pc_reg == Some(value),
+            Arg::Jump(rel) => value.checked_add(1) == self.start.checked_add(rel),
+            Arg::Immediate(name) => *bindings.entry(name).or_insert(value) == value,
+            Arg::Register(name) => match bindings.get(name) {
+                Some(&bound) => bound == value,
+                None => {
+                    let taken = bindings
+                        .iter()
+                        .any(|(n, &v)| !n.starts_with('#') && v == value);
+                    if self.pc_reg == Some(value) || taken {
+                        false
+                    } else {
+                        bindings.insert(name, value);
+                        true
+                    }
+                }
+            },
+        }
+    }
+
+    fn matches(&self, index: usize, bindings: Bindings) -> Option<Bindings> {
+        let Some(&(op, [a, b, c])) = self.pattern.get(index) else {
+            return Some(bindings);
+        };
+        let instr = self.program.get(self.start + index)?;
+        if instr.op != op {
+            return None;
+        }
+        let commutative = matches!(op, Op::Addr | Op::Mulr | Op::Banr | Op::Borr | Op::Eqrr);
+        let orders: &[(Number, Number)] = if commutative {
+            &[(instr.a, instr.b), (instr.b, instr.a)]
+        } else {
+            &[(instr.a, instr.b)]
+        };
+        orders.iter().find_map(|&(va, vb)| {
+            let mut attempt = bindings.clone();
+            if self.bind(&mut attempt, a, va)
+                && self.bind(&mut attempt, b, vb)
+                && self.bind(&mut attempt, c, instr.c)
+            {
+                self.matches(index + 1, attempt)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+fn find(cpu: &Cpu, start: Number, text: &'static str) -> Option<(Number, Bindings)> {
+    let matcher = Matcher {
+        program: &cpu.program,
+        pc_reg: cpu.pc_reg,
+        start,
+        pattern: pattern(text),
+    };
+    // Registers outside the register file are left to the interpreter's
+    // faults.
+    let count = cpu.registers.len();
+    matcher
+        .matches(0, HashMap::new())
+        .filter(|bindings| {
+            bindings
+                .iter()
+                .all(|(n, &v)| n.starts_with('#') || v < count)
+        })
+        .map(|bindings| (start + matcher.pattern.len(), bindings))
+}
+
+pub fn analyse(cpu: &Cpu) -> Vec<Shortcut> {
+    let mut shortcuts = vec![];
+    if cpu.pc_reg.is_some_and(|ip| ip >= cpu.registers.len()) {
+        return shortcuts;
+    }
+    for start in 0..cpu.program.len() {
+        let found = if let Some((exit, b)) = find(cpu, start, DIVISOR_SUM) {
+            Some((
+                exit,
+                Idiom::DivisorSum {
+                    n: b["N"],
+                    acc: b["ACC"],
+                    i: b["I"],
+                    j: b["J"],
+                    t: b["T"],
+                },
+            ))
+        } else if let Some((exit, b)) = find(cpu, start, DIVIDE).filter(|(_, b)| b["#K"] > 0) {
+            Some((
+                exit,
+                Idiom::Divide {
+                    dividend: b["X"],
+                    divisor: b["#K"],
+                    quotient: b["Q"],
+                    t: b["T"],
+                },
+            ))
+        } else {
+            find(cpu, start, MULTIPLY_ACCUMULATE).map(|(exit, b)| {
+                (
+                    exit,
+                    Idiom::MultiplyAccumulate {
+                        acc: b["ACC"],
+                        addend: b["A"],
+                        counter: b["CNT"],
+                        limit: b["N"],
+                        t: b["T"],
+                    },
+                )
+            })
+        };
+        if let Some((exit, idiom)) = found {
+            shortcuts.push(Shortcut { start, exit, idiom });
+        }
+    }
+    shortcuts
+}
+
+#[derive(Debug, Clone)]
+pub struct Optimized {
+    pub cpu: Cpu,
+    shortcuts: HashMap<Number, Shortcut>,
+}
+
+impl Optimized {
+    pub fn new(cpu: Cpu) -> Self {
+        let shortcuts = analyse(&cpu).into_iter().map(|s| (s.start, s)).collect();
+        Self { cpu, shortcuts }
+    }
+
+    pub fn shortcuts(&self) -> impl Iterator<Item = &Shortcut> {
+        self.shortcuts.values().sorted_by_key(|s| s.start)
+    }
+
+    fn step(&mut self, avoid: Option<usize>) {
+        let pc = self.cpu.pc as Number;
+        match self.shortcuts.get(&pc) {
+            Some(shortcut)
+                if !avoid.is_some_and(|t| (shortcut.start..shortcut.exit).contains(&t)) =>
+            {
+                let cpu = &mut self.cpu;
+                if let Err(e) = shortcut.apply(&mut cpu.registers, cpu.arithmetic, &cpu.program) {
+                    cpu.fault = Some(e);
+                    return;
+                }
+                if let Some(pc_reg) = self.cpu.pc_reg {
+                    self.cpu.registers[pc_reg] = shortcut.exit - 1;
+                }
+                self.cpu.pc = shortcut.exit as i32;
+                self.cpu.cycles += 1;
+            }
+            _ => self.cpu.apply(),
+        }
+    }
+
+    pub fn run(&mut self) {
+        while !self.cpu.halted() {
+            self.step(None);
+        }
+    }
+
+    pub fn run_till_pc(&mut self, target: usize) {
+        while !self.cpu.halted() {
+            self.step(Some(target));
+            if self.cpu.pc == target as i32 {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod analyse {
+    use super::*;
+
+    #[test]
+    fn divisor_sum() {
+        let cpu: Cpu = include_str!("divisor_sum.txt").parse().unwrap();
+        assert_eq!(
+            analyse(&cpu),
+            vec![Shortcut {
+                start: 1,
+                exit: 16,
+                idiom: Idiom::DivisorSum {
+                    n: 1,
+                    acc: 0,
+                    i: 2,
+                    j: 3,
+                    t: 4
+                }
+            }]
+        );
+    }
+
+    #[test]
+    fn divide() {
+        let cpu: Cpu = include_str!("hash.txt").parse().unwrap();
+        assert_eq!(
+            analyse(&cpu),
+            vec![Shortcut {
+                start: 12,
+                exit: 21,
+                idiom: Idiom::Divide {
+                    dividend: 2,
+                    divisor: 256,
+                    quotient: 3,
+                    t: 5
+                }
+            }]
+        );
+    }
+
+    #[test_case(3, 10 => (7 * 8, 11))]
+    #[test_case(10, 10 => (7, 11))]
+    #[test_case(12, 10 => (7, 13))]
+    fn multiply_accumulate(counter: Number, limit: Number) -> (Number, Number) {
+        let source = "#ip 5
+addi 0 0 0
+addr 1 2 1
+addi 3 1 3
+gtrr 3 4 0
+addr 5 0 5
+seti 0 0 5";
+        let cpu: Cpu = source.parse().unwrap();
+        let shortcuts = analyse(&cpu);
+        assert_eq!(shortcuts.len(), 1);
+        assert_eq!((shortcuts[0].start, shortcuts[0].exit), (1, 6));
+
+        let mut optimized = Optimized::new(cpu.clone());
+        let mut plain = cpu;
+        for cpu in [&mut optimized.cpu, &mut plain] {
+            cpu.registers[2] = 7;
+            cpu.registers[3] = counter;
+            cpu.registers[4] = limit;
+        }
+        optimized.run();
+        plain.run();
+        assert_eq!(optimized.cpu.registers, plain.registers);
+        (optimized.cpu.registers[1], optimized.cpu.registers[3])
+    }
+
+    #[test]
+    fn jump_operand_overflow() {
+        let cpu: Cpu = "#ip 5
+addr 1 2 1
+addi 3 1 3
+gtrr 3 4 0
+addr 5 0 5
+seti 18446744073709551615 0 5"
+            .parse()
+            .unwrap();
+        assert_eq!(analyse(&cpu), vec![]);
+    }
+
+    #[test]
+    fn missing_registers() {
+        let mut cpu: Cpu = include_str!("divisor_sum.txt").parse().unwrap();
+        cpu.registers.truncate(4);
+        assert_eq!(analyse(&cpu), vec![]);
+    }
+
+    #[test_case(Arithmetic::Wrapping)]
+    #[test_case(Arithmetic::Checked)]
+    #[test_case(Arithmetic::Saturating)]
+    fn arithmetic(arithmetic: Arithmetic) {
+        let mut cpu: Cpu = "#ip 5
+addi 0 0 0
+addr 1 2 1
+addi 3 1 3
+gtrr 3 4 0
+addr 5 0 5
+seti 0 0 5"
+            .parse()
+            .unwrap();
+        cpu.arithmetic = arithmetic;
+        cpu.registers[1] = 5;
+        cpu.registers[2] = Number::MAX / 3;
+        cpu.registers[4] = 10;
+        let mut optimized = Optimized::new(cpu.clone());
+        optimized.run();
+        cpu.run();
+        assert_eq!(optimized.cpu.fault, cpu.fault);
+        match arithmetic {
+            Arithmetic::Checked => assert_eq!(
+                cpu.fault,
+                Some(CpuError::Overflow {
+                    pc: 1,
+                    instr: cpu.program[1]
+                })
+            ),
+            _ => assert_eq!(optimized.cpu.registers, cpu.registers),
+        }
+    }
+
+    #[test_case(12 => Ok(1 + 2 + 3 + 4 + 6 + 12))]
+    #[test_case(1 << 33 => Err(2))]
+    #[test_case(Number::MAX - 1 => Err(2))]
+    fn divisor_sum_overflow(n: Number) -> Result<Number, Number> {
+        let idiom = Idiom::DivisorSum {
+            n: 0,
+            acc: 1,
+            i: 2,
+            j: 3,
+            t: 4,
+        };
+        let mut r = vec![n, 0, 0, 0, 0];
+        idiom.apply(&mut r, Arithmetic::Checked).map(|()| r[1])
+    }
+
+    #[test]
+    fn matches_interpreter() {
+        let mut cpu: Cpu = include_str!("divisor_sum.txt").parse().unwrap();
+        let mut optimized = Optimized::new(cpu.clone());
+        optimized.run();
+        cpu.run();
+        assert_eq!(optimized.cpu.registers, cpu.registers);
+        assert!(optimized.cpu.cycles < cpu.cycles);
+    }
+}
@@ -0,0 +1,212 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::ControlFlow,
+};
+
+use itertools::Itertools;
+
+use super::{disasm::disassemble, Cpu, Limits, Number, StopReason};
+
+#[derive(Debug, Clone)]
+pub struct Profiler {
+    pub hits: Vec<usize>,
+    pub taken: Vec<usize>,
+    pub not_taken: Vec<usize>,
+    pub histograms: BTreeMap<Number, Vec<HashMap<Number, usize>>>,
+}
+
+impl Profiler {
+    pub fn new(cpu: &Cpu) -> Self {
+        let len = cpu.program.len();
+        Self {
+            hits: vec![0; len],
+            taken: vec![0; len],
+            not_taken: vec![0; len],
+            histograms: BTreeMap::new(),
+        }
+    }
+
+    pub fn sample(&mut self, pc: Number, registers: usize) {
+        self.histograms
+            .entry(pc)
+            .or_insert_with(|| vec![HashMap::new(); registers]);
+    }
+
+    // Histograms sample the registers the instruction read, not what it left.
+    // The counters grow with the program if it has changed since `new`.
+    fn record(&mut self, pc: Number, before: &[Number], cpu: &Cpu) {
+        if pc >= self.hits.len() {
+            for counts in [&mut self.hits, &mut self.taken, &mut self.not_taken] {
+                counts.resize(cpu.program.len().max(pc + 1), 0);
+            }
+        }
+        self.hits[pc] += 1;
+        if cpu.program[pc].op.writes() && cpu.is_ip(cpu.program[pc].c) {
+            if cpu.pc() == pc as i32 + 1 {
+                self.not_taken[pc] += 1;
+            } else {
+                self.taken[pc] += 1;
+            }
+        }
+        if let Some(histogram) = self.histograms.get_mut(&pc) {
+            for (register, &value) in before.iter().enumerate() {
+                if !cpu.is_ip(register) {
+                    *histogram[register].entry(value).or_default() += 1;
+                }
+            }
+        }
+    }
+
+    pub fn run(&mut self, cpu: &mut Cpu, limits: &Limits) -> StopReason {
+        cpu.run_observed(limits, |pc, before, cpu| {
+            self.record(pc, before, cpu);
+            ControlFlow::Continue(())
+        })
+    }
+
+    pub fn hottest(&self, count: usize) -> Vec<(Number, usize)> {
+        self.hits
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|&(_, hits)| hits > 0)
+            .sorted_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)))
+            .take(count)
+            .collect()
+    }
+
+    pub fn report(&self, cpu: &Cpu) -> String {
+        let disassembly = disassemble(cpu);
+        let total: usize = self.hits.iter().sum();
+        let count = |counts: &[usize], pc: Number| counts.get(pc).copied().unwrap_or(0);
+        let mut report = String::new();
+        for pc in 0..cpu.program.len() {
+            let hits = count(&self.hits, pc);
+            let branch = if cpu.program[pc].op.writes() && cpu.is_ip(cpu.program[pc].c) {
+                format!("{}/{}", count(&self.taken, pc), count(&self.not_taken, pc))
+            } else {
+                String::new()
+            };
+            report.push_str(&format!(
+                "{:>12} {:>17}  {}\n",
+                hits,
+                branch,
+                disassembly.line(pc)
+            ));
+            if let Some(histogram) = self.histograms.get(&pc) {
+                for (register, values) in histogram.iter().enumerate() {
+                    if values.is_empty() {
+                        continue;
+                    }
+                    let common = values
+                        .iter()
+                        .sorted_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)))
+                        .take(5)
+                        .map(|(value, count)| format!("{}x{}", value, count))
+                        .join(" ");
+                    let distinct = values.len();
+                    report.push_str(&format!(
+                        "{:>32}r{}: {} ({} distinct)\n",
+                        "", register, common, distinct
+                    ));
+                }
+            }
+        }
+
+        report.push_str(&format!("{} instructions executed\n", total));
+        // Nothing ran: no shares to report, and no zero to divide by.
+        if total > 0 {
+            for (pc, hits) in self.hottest(5) {
+                if pc >= cpu.program.len() {
+                    continue;
+                }
+                report.push_str(&format!(
+                    "{:>6.2}%  {}\n",
+                    100.0 * hits as f64 / total as f64,
+                    disassembly.line(pc)
+                ));
+            }
+        }
+        for &header in &disassembly.loops {
+            report.push_str(&format!(
+                "loop at L{} iterated {} times\n",
+                header,
+                count(&self.hits, header)
+            ));
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod profiler {
+    use super::*;
+
+    fn counter() -> Cpu {
+        "#ip 2
+seti 0 0 1
+addi 1 1 1
+eqri 1 10 3
+addr 3 2 2
+seti 0 0 2
+mulr 2 2 2"
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn counts() {
+        let mut cpu = counter();
+        let mut profiler = Profiler::new(&cpu);
+        profiler.sample(2, cpu.registers.len());
+        assert_eq!(
+            profiler.run(&mut cpu, &Limits::default()),
+            StopReason::Halted
+        );
+        assert_eq!(profiler.hits, vec![1, 10, 10, 10, 9, 1]);
+        assert_eq!(profiler.taken[3], 1);
+        assert_eq!(profiler.not_taken[3], 9);
+        assert_eq!(profiler.taken[4], 9);
+        assert_eq!(profiler.hottest(2), vec![(1, 10), (2, 10)]);
+        let histogram = &profiler.histograms[&2];
+        assert_eq!(histogram[1].len(), 10);
+        assert_eq!(histogram[1].keys().min(), Some(&1));
+        assert_eq!(histogram[3][&0], 10);
+        assert_eq!(histogram[3].get(&1), None);
+    }
+
+    #[test]
+    fn report() {
+        let mut cpu = counter();
+        let mut profiler = Profiler::new(&cpu);
+        profiler.run(&mut cpu, &Limits::default());
+        let report = profiler.report(&cpu);
+        assert!(report.contains("          10               1/9         3: if r1 == 10 goto L5"));
+        assert!(report.contains("41 instructions executed"));
+        assert!(report.contains("loop at L1 iterated 10 times"));
+    }
+
+    #[test]
+    fn program_changes() {
+        let mut cpu = counter();
+        let mut profiler = Profiler::new(&"seti 0 0 1".parse().unwrap());
+        profiler.run(&mut cpu, &Limits::default());
+        assert_eq!(profiler.hits, vec![1, 10, 10, 10, 9, 1]);
+        assert_eq!(profiler.taken[4], 9);
+
+        let short: Cpu = "seti 0 0 1".parse().unwrap();
+        let report = profiler.report(&short);
+        assert!(report.contains("41 instructions executed"));
+        assert!(report.contains("0: r1 = 0"), "{}", report);
+    }
+
+    #[test]
+    fn empty_run() {
+        let mut cpu = counter();
+        let profiler = Profiler::new(&cpu);
+        cpu.run();
+        let report = profiler.report(&cpu);
+        assert!(report.contains("0 instructions executed"));
+        assert!(!report.contains("NaN"));
+    }
+}
@@ -0,0 +1,540 @@
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use itertools::Itertools;
+use strum::IntoEnumIterator;
+
+use super::{Arithmetic, Cpu, CpuError, Dialect, Instruction, Number, Op};
+
+const MAGIC: &[u8; 4] = b"ELFC";
+// Version 2 added the fault; version 1 snapshots load as healthy.
+const VERSION: u8 = 2;
+const UNBOUND: u64 = u64::MAX;
+
+fn fault_to_text(fault: &CpuError) -> String {
+    match fault {
+        CpuError::InvalidRegister { pc, register } => format!("register {} {}", pc, register),
+        CpuError::Overflow { pc, instr } => format!("overflow {} {}", pc, instr),
+        CpuError::DivisionByZero { pc, instr } => format!("division {} {}", pc, instr),
+        CpuError::CycleBudget { cycles } => format!("budget {}", cycles),
+        CpuError::PcOutOfRange { pc } => format!("pc {}", pc),
+    }
+}
+
+fn fault_from_text(text: &str) -> anyhow::Result<CpuError> {
+    let (kind, rest) = text.split_once(' ').context("missing fault details")?;
+    let (first, rest) = rest.split_once(' ').unwrap_or((rest, ""));
+    let instr = || Instruction::parse(rest, Dialect::Extended);
+    let fault = match kind {
+        "register" => CpuError::InvalidRegister {
+            pc: first.parse()?,
+            register: rest.parse()?,
+        },
+        "overflow" => CpuError::Overflow {
+            pc: first.parse()?,
+            instr: instr()?,
+        },
+        "division" => CpuError::DivisionByZero {
+            pc: first.parse()?,
+            instr: instr()?,
+        },
+        "budget" if rest.is_empty() => CpuError::CycleBudget {
+            cycles: first.parse()?,
+        },
+        "pc" if rest.is_empty() => CpuError::PcOutOfRange { pc: first.parse()? },
+        _ => bail!("unknown fault"),
+    };
+    Ok(fault)
+}
+
+pub fn to_text(cpu: &Cpu) -> String {
+    let fault = match &cpu.fault {
+        Some(fault) => format!("#fault {}\n", fault_to_text(fault)),
+        None => String::new(),
+    };
+    format!(
+        "#registers {}\n#pc {}\n#cycles {}\n#arithmetic {}\n{}{}",
+        cpu.registers.iter().join(" "),
+        cpu.pc,
+        cpu.cycles,
+        cpu.arithmetic,
+        fault,
+        cpu
+    )
+}
+
+pub fn from_text(text: &str) -> anyhow::Result<Cpu> {
+    let mut registers = None;
+    let mut pc = 0;
+    let mut cycles = 0;
+    let mut arithmetic = Arithmetic::default();
+    let mut fault = None;
+    let mut program = vec![];
+    for line in text.lines() {
+        if let Some(values) = line.strip_prefix("#registers ") {
+            registers = Some(
+                values
+                    .split_ascii_whitespace()
+                    .map(|v| v.parse())
+                    .collect::<Result<Vec<Number>, _>>()
+                    .with_context(|| format!("bad registers {:?}", values))?,
+            );
+        } else if let Some(value) = line.strip_prefix("#pc ") {
+            pc = value
+                .parse()
+                .with_context(|| format!("bad pc {:?}", value))?;
+        } else if let Some(value) = line.strip_prefix("#cycles ") {
+            cycles = value
+                .parse()
+                .with_context(|| format!("bad cycles {:?}", value))?;
+        } else if let Some(value) = line.strip_prefix("#arithmetic ") {
+            arithmetic = value
+                .parse()
+                .with_context(|| format!("bad arithmetic {:?}", value))?;
+        } else if let Some(value) = line.strip_prefix("#fault ") {
+            fault = Some(fault_from_text(value).with_context(|| format!("bad fault {:?}", value))?);
+        } else {
+            program.push(line);
+        }
+    }
+
+    let count = registers
+        .as_ref()
+        .map_or(Cpu::default().registers.len(), Vec::len);
+    let mut cpu = Cpu::parse(&program.join("\n"), count, Dialect::Extended)?;
+    if let Some(registers) = registers {
+        cpu.registers = registers;
+    }
+    cpu.pc = pc;
+    cpu.cycles = cycles;
+    cpu.arithmetic = arithmetic;
+    cpu.fault = fault;
+    Ok(cpu)
+}
+
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn instruction(&mut self, instr: &Instruction) {
+        self.u64(Op::iter().position(|op| op == instr.op).unwrap() as u64);
+        self.u64(instr.a as u64);
+        self.u64(instr.b as u64);
+        self.u64(instr.c as u64);
+    }
+
+    // A kind, 0 for none, then the fault's fields.
+    fn fault(&mut self, fault: Option<&CpuError>) {
+        match fault {
+            None => self.u64(0),
+            Some(&CpuError::InvalidRegister { pc, register }) => {
+                self.u64(1);
+                self.u64(pc as u64);
+                self.u64(register as u64);
+            }
+            Some(CpuError::Overflow { pc, instr }) => {
+                self.u64(2);
+                self.u64(*pc as u64);
+                self.instruction(instr);
+            }
+            Some(CpuError::DivisionByZero { pc, instr }) => {
+                self.u64(3);
+                self.u64(*pc as u64);
+                self.instruction(instr);
+            }
+            Some(&CpuError::CycleBudget { cycles }) => {
+                self.u64(4);
+                self.u64(cycles as u64);
+            }
+            Some(&CpuError::PcOutOfRange { pc }) => {
+                self.u64(5);
+                self.u64(pc as i64 as u64);
+            }
+        }
+    }
+}
+
+pub fn to_bytes(cpu: &Cpu) -> Vec<u8> {
+    let mut writer = Writer {
+        bytes: MAGIC.to_vec(),
+    };
+    writer.bytes.push(VERSION);
+    writer.u64(cpu.pc_reg.map_or(UNBOUND, |r| r as u64));
+    writer.u64(cpu.pc as i64 as u64);
+    writer.u64(cpu.cycles as u64);
+    writer.u64(
+        Arithmetic::iter()
+            .position(|a| a == cpu.arithmetic)
+            .unwrap() as u64,
+    );
+    writer.fault(cpu.fault.as_ref());
+    writer.u64(cpu.registers.len() as u64);
+    for &register in &cpu.registers {
+        writer.u64(register as u64);
+    }
+    writer.u64(cpu.program.len() as u64);
+    for instr in &cpu.program {
+        writer.instruction(instr);
+    }
+    writer.bytes
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn u64(&mut self) -> anyhow::Result<u64> {
+        if self.bytes.len() < 8 {
+            bail!("snapshot is truncated");
+        }
+        let (value, rest) = self.bytes.split_at(8);
+        self.bytes = rest;
+        Ok(u64::from_le_bytes(value.try_into().unwrap()))
+    }
+
+    fn number(&mut self) -> anyhow::Result<Number> {
+        Ok(Number::try_from(self.u64()?)?)
+    }
+
+    fn pc(&mut self) -> anyhow::Result<i32> {
+        Ok(i32::try_from(self.u64()? as i64)?)
+    }
+
+    fn instruction(&mut self) -> anyhow::Result<Instruction> {
+        let op = Op::iter().nth(self.number()?).context("bad opcode")?;
+        Ok(Instruction::new(
+            op,
+            self.number()?,
+            self.number()?,
+            self.number()?,
+        ))
+    }
+
+    fn fault(&mut self) -> anyhow::Result<Option<CpuError>> {
+        let fault = match self.u64()? {
+            0 => return Ok(None),
+            1 => CpuError::InvalidRegister {
+                pc: self.number()?,
+                register: self.number()?,
+            },
+            2 => CpuError::Overflow {
+                pc: self.number()?,
+                instr: self.instruction()?,
+            },
+            3 => CpuError::DivisionByZero {
+                pc: self.number()?,
+                instr: self.instruction()?,
+            },
+            4 => CpuError::CycleBudget {
+                cycles: self.number()?,
+            },
+            5 => CpuError::PcOutOfRange { pc: self.pc()? },
+            kind => bail!("bad fault kind {}", kind),
+        };
+        Ok(Some(fault))
+    }
+}
+
+pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Cpu> {
+    let Some(rest) = bytes.strip_prefix(MAGIC) else {
+        bail!("not a cpu snapshot");
+    };
+    let version = match rest.first() {
+        Some(&version @ (1 | VERSION)) => version,
+        Some(version) => bail!("unsupported snapshot version {}", version),
+        None => bail!("snapshot is truncated"),
+    };
+    let mut reader = Reader { bytes: &rest[1..] };
+
+    let pc_reg = match reader.u64()? {
+        UNBOUND => None,
+        r => Some(Number::try_from(r)?),
+    };
+    let pc = reader.pc()?;
+    let cycles = reader.number()?;
+    let arithmetic = Arithmetic::iter()
+        .nth(reader.number()?)
+        .context("bad arithmetic policy")?;
+    let fault = match version {
+        1 => None,
+        _ => reader.fault()?,
+    };
+    let registers = (0..reader.number()?)
+        .map(|_| reader.number())
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let count = reader.number()?;
+    let program = (0..count)
+        .map(|_| reader.instruction())
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if !reader.bytes.is_empty() {
+        bail!("{} trailing bytes in snapshot", reader.bytes.len());
+    }
+    if let Some(pc_reg) = pc_reg.filter(|&r| r >= registers.len()) {
+        bail!(
+            "ip register {} is outside {} registers",
+            pc_reg,
+            registers.len()
+        );
+    }
+    for (pc, instr) in program.iter().enumerate() {
+        if let Some(register) = instr.invalid_register(None, registers.len()) {
+            bail!(
+                "{:?} at pc {} names register {}, outside {} registers",
+                instr.to_string(),
+                pc,
+                register,
+                registers.len()
+            );
+        }
+    }
+
+    Ok(Cpu {
+        registers,
+        pc,
+        pc_reg,
+        cycles,
+        program,
+        arithmetic,
+        fault,
+    })
+}
+
+pub fn save(cpu: &Cpu, path: impl AsRef<Path>) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let data = if path.extension().is_some_and(|e| e == "bin") {
+        to_bytes(cpu)
+    } else {
+        to_text(cpu).into_bytes()
+    };
+    std::fs::write(path, data).with_context(|| format!("writing {}", path.display()))
+}
+
+pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Cpu> {
+    let path = path.as_ref();
+    let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    if data.starts_with(MAGIC) {
+        from_bytes(&data)
+    } else {
+        from_text(std::str::from_utf8(&data)?)
+    }
+    .with_context(|| format!("loading {}", path.display()))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    Pc(i32, i32),
+    PcReg(Option<usize>, Option<usize>),
+    Cycles(usize, usize),
+    Arithmetic(Arithmetic, Arithmetic),
+    Fault(Option<CpuError>, Option<CpuError>),
+    Register(usize, Option<Number>, Option<Number>),
+    Instruction(Number, Option<Instruction>, Option<Instruction>),
+}
+
+impl std::fmt::Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let show = |v: &Option<String>| v.clone().unwrap_or_else(|| "-".to_string());
+        match self {
+            Difference::Pc(a, b) => write!(f, "pc: {} -> {}", a, b),
+            Difference::PcReg(a, b) => write!(
+                f,
+                "#ip: {} -> {}",
+                show(&a.map(|r| r.to_string())),
+                show(&b.map(|r| r.to_string()))
+            ),
+            Difference::Cycles(a, b) => write!(f, "cycles: {} -> {}", a, b),
+            Difference::Arithmetic(a, b) => write!(f, "arithmetic: {} -> {}", a, b),
+            Difference::Fault(a, b) => write!(
+                f,
+                "fault: {} -> {}",
+                show(&a.map(|e| e.to_string())),
+                show(&b.map(|e| e.to_string()))
+            ),
+            Difference::Register(r, a, b) => write!(
+                f,
+                "r{}: {} -> {}",
+                r,
+                show(&a.map(|v| v.to_string())),
+                show(&b.map(|v| v.to_string()))
+            ),
+            Difference::Instruction(pc, a, b) => write!(
+                f,
+                "{}: {} -> {}",
+                pc,
+                show(&a.map(|i| i.to_string())),
+                show(&b.map(|i| i.to_string()))
+            ),
+        }
+    }
+}
+
+pub fn diff(a: &Cpu, b: &Cpu) -> Vec<Difference> {
+    let mut differences = vec![];
+    if a.pc != b.pc {
+        differences.push(Difference::Pc(a.pc, b.pc));
+    }
+    if a.pc_reg != b.pc_reg {
+        differences.push(Difference::PcReg(a.pc_reg, b.pc_reg));
+    }
+    if a.cycles != b.cycles {
+        differences.push(Difference::Cycles(a.cycles, b.cycles));
+    }
+    if a.arithmetic != b.arithmetic {
+        differences.push(Difference::Arithmetic(a.arithmetic, b.arithmetic));
+    }
+    if a.fault != b.fault {
+        differences.push(Difference::Fault(a.fault, b.fault));
+    }
+    for r in 0..a.registers.len().max(b.registers.len()) {
+        let (x, y) = (a.registers.get(r), b.registers.get(r));
+        if x != y {
+            differences.push(Difference::Register(r, x.copied(), y.copied()));
+        }
+    }
+    for pc in 0..a.program.len().max(b.program.len()) {
+        let (x, y) = (a.program.get(pc), b.program.get(pc));
+        if x != y {
+            differences.push(Difference::Instruction(pc, x.copied(), y.copied()));
+        }
+    }
+    differences
+}
+
+#[cfg(test)]
+mod round_trip {
+    use super::*;
+
+    fn midway() -> Cpu {
+        let mut cpu: Cpu = include_str!("hash.txt").parse().unwrap();
+        cpu.arithmetic = Arithmetic::Wrapping;
+        cpu.run_for(1234);
+        cpu
+    }
+
+    fn assert_same(a: &Cpu, b: &Cpu) {
+        assert_eq!(diff(a, b), vec![]);
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let cpu = midway();
+        let text = to_text(&cpu);
+        assert!(text.starts_with("#registers "));
+        assert_same(&from_text(&text).unwrap(), &cpu);
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let cpu = midway();
+        let bytes = to_bytes(&cpu);
+        assert_same(&from_bytes(&bytes).unwrap(), &cpu);
+        assert!(from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(from_bytes(b"nope").is_err());
+    }
+
+    #[test]
+    fn faults() {
+        let mut cpu: Cpu = include_str!("hash.txt").parse().unwrap();
+        cpu.run_for(10);
+        let healthy = cpu.clone();
+        let faults = [
+            CpuError::InvalidRegister { pc: 3, register: 9 },
+            CpuError::Overflow {
+                pc: 6,
+                instr: cpu.program[6],
+            },
+            CpuError::DivisionByZero {
+                pc: 2,
+                instr: Instruction::new(Op::Divi, 1, 0, 2),
+            },
+            CpuError::CycleBudget { cycles: 10 },
+            CpuError::PcOutOfRange { pc: -1 },
+        ];
+        for fault in faults {
+            cpu.fault = Some(fault);
+            let text = to_text(&cpu);
+            assert!(text.contains("\n#fault "), "{}", text);
+            for restored in [from_text(&text), from_bytes(&to_bytes(&cpu))] {
+                let mut restored = restored.unwrap();
+                assert_same(&restored, &cpu);
+                assert!(restored.halted());
+                restored.run();
+                assert_eq!(restored.cycles, cpu.cycles);
+            }
+        }
+        assert_eq!(
+            diff(&healthy, &cpu)
+                .iter()
+                .map(|d| d.to_string())
+                .collect_vec(),
+            vec!["fault: - -> pc -1 is outside the program"]
+        );
+    }
+
+    #[test]
+    fn version_1() {
+        let cpu = midway();
+        let mut bytes = to_bytes(&cpu);
+        bytes[4] = 1;
+        bytes.drain(5 + 4 * 8..5 + 5 * 8);
+        assert_same(&from_bytes(&bytes).unwrap(), &cpu);
+    }
+
+    #[test]
+    fn rejects() {
+        let mut cpu = midway();
+        cpu.program.push(Instruction::new(Op::Addr, 0, 9, 1));
+        assert_eq!(
+            from_bytes(&to_bytes(&cpu)).unwrap_err().to_string(),
+            "\"addr 0 9 1\" at pc 26 names register 9, outside 6 registers"
+        );
+        let text = to_text(&midway()).replace("#arithmetic", "#fault lost 3\n#arithmetic");
+        assert_eq!(
+            format!("{:#}", from_text(&text).unwrap_err()),
+            "bad fault \"lost 3\": unknown fault"
+        );
+    }
+
+    #[test]
+    fn resume() {
+        let mut whole: Cpu = include_str!("hash.txt").parse().unwrap();
+        whole.run_for(5000);
+        let mut resumed = from_bytes(&to_bytes(&midway())).unwrap();
+        resumed.arithmetic = Arithmetic::default();
+        resumed.run_for(5000 - 1234);
+        assert_same(&resumed, &whole);
+    }
+
+    #[test]
+    fn differences() {
+        let a = midway();
+        let mut b = a.clone();
+        b.run_for(1);
+        b.registers.push(7);
+        b.program[0].a = 124;
+        let differences = diff(&a, &b).iter().map(|d| d.to_string()).collect_vec();
+        assert_eq!(differences[1], "cycles: 1234 -> 1235");
+        assert_eq!(differences.last().unwrap(), "0: seti 0 0 1 -> seti 124 0 1");
+        assert!(differences.contains(&"r6: - -> 7".to_string()));
+    }
+
+    #[test]
+    fn files() {
+        let cpu = midway();
+        let dir =
+            std::env::temp_dir().join(format!("elfcode-snapshot-{}-files", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["snapshot.txt", "snapshot.bin"] {
+            let path = dir.join(name);
+            save(&cpu, &path).unwrap();
+            assert_same(&load(&path).unwrap(), &cpu);
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,580 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, BinaryHeap, HashMap},
+};
+
+use super::{Arithmetic, Cpu, CpuError, Number, Op};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    Known(Number),
+    Linear { a: i128, b: i128 },
+}
+
+impl Value {
+    fn linear(self) -> (i128, i128) {
+        match self {
+            Value::Known(v) => (0, v as i128),
+            Value::Linear { a, b } => (a, b),
+        }
+    }
+
+    fn from_linear(a: i128, b: i128) -> Option<Value> {
+        if a != 0 {
+            Some(Value::Linear { a, b })
+        } else {
+            Number::try_from(b).ok().map(Value::Known)
+        }
+    }
+
+    fn at(self, r0: Number) -> Option<Number> {
+        let (a, b) = self.linear();
+        Number::try_from(a.checked_mul(r0 as i128)?.checked_add(b)?).ok()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Domain {
+    pub lo: Number,
+    pub hi: Number,
+    pub excluded: BTreeSet<Number>,
+}
+
+impl Default for Domain {
+    fn default() -> Self {
+        Self {
+            lo: 0,
+            hi: Number::MAX,
+            excluded: BTreeSet::new(),
+        }
+    }
+}
+
+impl Domain {
+    pub fn min(&self) -> Option<Number> {
+        (self.lo..=self.hi).find(|v| !self.excluded.contains(v))
+    }
+
+    pub fn contains(&self, value: Number) -> bool {
+        (self.lo..=self.hi).contains(&value) && !self.excluded.contains(&value)
+    }
+
+    fn single(&self) -> Option<Number> {
+        (self.lo == self.hi).then_some(self.lo)
+    }
+
+    fn clamp(mut self) -> Option<Domain> {
+        self.excluded.retain(|v| (self.lo..=self.hi).contains(v));
+        self.min().is_some().then_some(self)
+    }
+
+    fn at_least(&self, lo: i128) -> Option<Domain> {
+        let lo = Number::try_from(lo.max(0)).ok()?;
+        Domain {
+            lo: lo.max(self.lo),
+            ..self.clone()
+        }
+        .clamp()
+    }
+
+    fn at_most(&self, hi: i128) -> Option<Domain> {
+        let hi = Number::try_from(hi).ok()?;
+        Domain {
+            hi: hi.min(self.hi),
+            ..self.clone()
+        }
+        .clamp()
+    }
+
+    fn only(&self, value: i128) -> Option<Domain> {
+        let value = Number::try_from(value).ok()?;
+        self.contains(value).then(|| Domain {
+            lo: value,
+            hi: value,
+            excluded: BTreeSet::new(),
+        })
+    }
+
+    fn except(&self, value: i128) -> Option<Domain> {
+        let mut domain = self.clone();
+        if let Ok(value) = Number::try_from(value) {
+            domain.excluded.insert(value);
+        }
+        domain.clamp()
+    }
+
+    fn split(&self, op: Op, d: i128, e: i128) -> (Option<Domain>, Option<Domain>) {
+        use Op::*;
+        if d == 0 {
+            // Both sides move with r0 alike, so the comparison never changes.
+            let holds = match op {
+                Eqir | Eqri | Eqrr => e == 0,
+                _ => e > 0,
+            };
+            return match holds {
+                true => (Some(self.clone()), None),
+                false => (None, Some(self.clone())),
+            };
+        }
+        match op {
+            Eqir | Eqri | Eqrr if (-e).rem_euclid(d) == 0 => {
+                let point = (-e).div_euclid(d);
+                (self.only(point), self.except(point))
+            }
+            Eqir | Eqri | Eqrr => (None, Some(self.clone())),
+            _ if d > 0 => {
+                let first = (-e).div_euclid(d) + 1;
+                (self.at_least(first), self.at_most(first - 1))
+            }
+            _ => {
+                let last = (e - 1).div_euclid(-d);
+                (self.at_most(last), self.at_least(last + 1))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Halted,
+    CycleLimit,
+    BranchLimit,
+    PathLimit,
+    Unsupported(Number),
+    Fault(CpuError),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path {
+    pub domain: Domain,
+    pub cycles: usize,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    pub max_cycles: usize,
+    pub max_branches: usize,
+    pub max_paths: usize,
+}
+
+impl Default for Bounds {
+    fn default() -> Self {
+        Self {
+            max_cycles: 1_000_000,
+            max_branches: 64,
+            max_paths: 10_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct State {
+    registers: Vec<Value>,
+    pc: Number,
+    cycles: usize,
+    branches: usize,
+    domain: Domain,
+}
+
+impl State {
+    fn finish(self, outcome: Outcome) -> Step {
+        Step::Done(Path {
+            domain: self.domain,
+            cycles: self.cycles,
+            outcome,
+        })
+    }
+
+    fn restrict(&self, domain: Domain) -> Option<State> {
+        let mut state = State {
+            domain,
+            ..self.clone()
+        };
+        if let Some(r0) = state.domain.single() {
+            for register in state.registers.iter_mut() {
+                *register = Value::Known(register.at(r0)?);
+            }
+        }
+        Some(state)
+    }
+}
+
+enum Step {
+    Continue(State),
+    Fork(Vec<State>),
+    Done(Path),
+}
+
+enum Entry {
+    Running(State),
+    Finished(Path),
+}
+
+pub struct Paths<'a> {
+    cpu: &'a Cpu,
+    bounds: Bounds,
+    queue: BinaryHeap<Reverse<(usize, usize)>>,
+    entries: HashMap<usize, Entry>,
+    created: usize,
+}
+
+impl<'a> Paths<'a> {
+    pub fn new(cpu: &'a Cpu, bounds: Bounds) -> Self {
+        let mut registers = cpu
+            .registers
+            .iter()
+            .map(|&v| Value::Known(v))
+            .collect::<Vec<_>>();
+        if let Some(r0) = registers.first_mut() {
+            *r0 = Value::Linear { a: 1, b: 0 };
+        }
+        let mut paths = Self {
+            cpu,
+            bounds,
+            queue: BinaryHeap::new(),
+            entries: HashMap::new(),
+            created: 0,
+        };
+        let pc = usize::try_from(cpu.pc()).unwrap_or(Number::MAX);
+        paths.push(Entry::Running(State {
+            registers,
+            pc,
+            cycles: cpu.cycles,
+            branches: 0,
+            domain: Domain::default(),
+        }));
+        paths
+    }
+
+    fn push(&mut self, entry: Entry) {
+        let cycles = match &entry {
+            Entry::Running(state) => state.cycles,
+            Entry::Finished(path) => path.cycles,
+        };
+        self.queue.push(Reverse((cycles, self.created)));
+        self.entries.insert(self.created, entry);
+        self.created += 1;
+    }
+
+    fn step(&self, mut state: State) -> Step {
+        let Some(&instr) = self.cpu.program.get(state.pc) else {
+            return state.finish(Outcome::Halted);
+        };
+        if state.cycles >= self.bounds.max_cycles {
+            return state.finish(Outcome::CycleLimit);
+        }
+        let pc = state.pc;
+        state.cycles += 1;
+
+        if let Some(register) = instr.invalid_register(self.cpu.pc_reg, state.registers.len()) {
+            return state.finish(Outcome::Fault(CpuError::InvalidRegister { pc, register }));
+        }
+        if let Some(pc_reg) = self.cpu.pc_reg {
+            state.registers[pc_reg] = Value::Known(pc);
+        }
+        let (ra, rb) = instr.op.reads();
+        let a = if ra {
+            state.registers[instr.a]
+        } else {
+            Value::Known(instr.a)
+        };
+        let b = if rb {
+            state.registers[instr.b]
+        } else {
+            Value::Known(instr.b)
+        };
+        let (la, lb) = (a.linear(), b.linear());
+
+        use Op::*;
+        match instr.op {
+            Nop => {
+                state.pc += 1;
+                return Step::Continue(state);
+            }
+            Halt => return state.finish(Outcome::Halted),
+            _ => {}
+        }
+        let result = match (instr.op, a, b) {
+            (op, Value::Known(a), Value::Known(b)) => {
+                op.evaluate(a, b, Arithmetic::Wrapping).map(Value::Known)
+            }
+            (Setr | Seti, a, _) => Some(a),
+            (Addr | Addi, _, _) => {
+                la.0.checked_add(lb.0)
+                    .zip(la.1.checked_add(lb.1))
+                    .and_then(|(a, b)| Value::from_linear(a, b))
+            }
+            (Mulr | Muli, x, Value::Known(k)) | (Mulr | Muli, Value::Known(k), x) => {
+                let (xa, xb) = x.linear();
+                let k = k as i128;
+                xa.checked_mul(k)
+                    .zip(xb.checked_mul(k))
+                    .and_then(|(a, b)| Value::from_linear(a, b))
+            }
+            (Gtir | Gtri | Gtrr | Eqir | Eqri | Eqrr, _, _) => {
+                let (Some(d), Some(e)) = (la.0.checked_sub(lb.0), la.1.checked_sub(lb.1)) else {
+                    return state.finish(Outcome::Unsupported(pc));
+                };
+                let (taken, not_taken) = state.domain.split(instr.op, d, e);
+                let mut children = [(taken, 1), (not_taken, 0)]
+                    .into_iter()
+                    .filter_map(|(domain, flag)| {
+                        let mut child = state.restrict(domain?)?;
+                        child.registers[instr.c] = Value::Known(flag);
+                        child.pc = if self.cpu.is_ip(instr.c) { flag } else { pc } + 1;
+                        Some(child)
+                    })
+                    .collect::<Vec<_>>();
+                return match children.len() {
+                    0 => state.finish(Outcome::Unsupported(pc)),
+                    1 => Step::Continue(children.pop().unwrap()),
+                    _ if state.branches >= self.bounds.max_branches => {
+                        state.finish(Outcome::BranchLimit)
+                    }
+                    _ => {
+                        for child in children.iter_mut() {
+                            child.branches += 1;
+                        }
+                        Step::Fork(children)
+                    }
+                };
+            }
+            _ => None,
+        };
+        match result {
+            Some(value) => {
+                state.registers[instr.c] = value;
+                self.advance(state, instr.c)
+            }
+            None => state.finish(Outcome::Unsupported(pc)),
+        }
+    }
+
+    fn advance(&self, mut state: State, written: usize) -> Step {
+        if self.cpu.pc_reg != Some(written) {
+            state.pc += 1;
+            return Step::Continue(state);
+        }
+        match state.registers[written] {
+            Value::Known(pc) => {
+                state.pc = pc.wrapping_add(1);
+                Step::Continue(state)
+            }
+            Value::Linear { a, b } => {
+                let mut rest = Some(state.domain.clone());
+                let mut children = vec![];
+                for target in 0..self.cpu.program.len() as i128 {
+                    if (target - 1 - b).rem_euclid(a) != 0 {
+                        continue;
+                    }
+                    let r0 = (target - 1 - b).div_euclid(a);
+                    if let Some(mut child) = state.domain.only(r0).and_then(|d| state.restrict(d)) {
+                        child.pc = target as Number;
+                        children.push(child);
+                        rest = rest.and_then(|d| d.except(r0));
+                    }
+                }
+                match rest {
+                    Some(domain) if children.is_empty() => {
+                        State { domain, ..state }.finish(Outcome::Halted)
+                    }
+                    Some(domain) => {
+                        children.push(State {
+                            domain,
+                            pc: Number::MAX,
+                            ..state
+                        });
+                        Step::Fork(children)
+                    }
+                    None if children.len() == 1 => Step::Continue(children.pop().unwrap()),
+                    None => Step::Fork(children),
+                }
+            }
+        }
+    }
+
+    fn run(&mut self, mut state: State) {
+        loop {
+            match self.step(state) {
+                Step::Continue(next) => state = next,
+                Step::Fork(children) => {
+                    for child in children {
+                        if self.created >= self.bounds.max_paths {
+                            self.push(Entry::Finished(Path {
+                                domain: child.domain,
+                                cycles: child.cycles,
+                                outcome: Outcome::PathLimit,
+                            }));
+                        } else {
+                            self.push(Entry::Running(child));
+                        }
+                    }
+                    return;
+                }
+                Step::Done(path) => {
+                    self.push(Entry::Finished(path));
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for Paths<'_> {
+    type Item = Path;
+
+    fn next(&mut self) -> Option<Path> {
+        loop {
+            let Reverse((_, id)) = self.queue.pop()?;
+            match self.entries.remove(&id).unwrap() {
+                Entry::Finished(path) => return Some(path),
+                Entry::Running(state) => self.run(state),
+            }
+        }
+    }
+}
+
+pub fn fewest_cycles_to_halt(cpu: &Cpu, bounds: Bounds) -> Option<(Number, usize)> {
+    let mut halting = Paths::new(cpu, bounds).filter(|p| p.outcome == Outcome::Halted);
+    let first = halting.next()?;
+    let r0 = halting
+        .take_while(|p| p.cycles == first.cycles)
+        .chain([first.clone()])
+        .filter_map(|p| p.domain.min())
+        .min()?;
+    Some((r0, first.cycles))
+}
+
+#[cfg(test)]
+mod paths {
+    use super::*;
+    use crate::wasm::Instruction;
+    use test_case::test_case;
+
+    #[test]
+    fn day21() {
+        let cpu: Cpu = include_str!("hash.txt").parse().unwrap();
+        let (r0, cycles) = fewest_cycles_to_halt(&cpu, Bounds::default()).unwrap();
+        assert_eq!(r0, 47893);
+        let mut concrete = cpu.clone();
+        concrete.registers[0] = r0;
+        concrete.run();
+        assert_eq!(concrete.cycles, cycles);
+    }
+
+    #[test]
+    fn comparisons() {
+        let cpu: Cpu = "#ip 5
+gtri 0 10 1
+addr 1 5 5
+seti 99 0 5
+muli 0 3 2
+eqri 2 33 1
+addr 1 5 5
+seti 99 0 5"
+            .parse()
+            .unwrap();
+        let paths = Paths::new(&cpu, Bounds::default()).collect::<Vec<_>>();
+        let summary = paths
+            .iter()
+            .map(|p| (p.cycles, p.domain.min(), p.domain.contains(12)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (3, Some(0), false),
+                (5, Some(11), false),
+                (6, Some(12), true)
+            ]
+        );
+        assert_eq!(fewest_cycles_to_halt(&cpu, Bounds::default()), Some((0, 3)));
+    }
+
+    // Both sides of these compare r0 with itself.
+    #[test_case("eqrr 0 0 1" => vec![(1, Some(0), Outcome::Halted)]; "equal")]
+    #[test_case("gtrr 0 0 1" => vec![(1, Some(0), Outcome::Halted)]; "greater")]
+    #[test_case("eqrr 0 0 5" => vec![(1, Some(0), Outcome::Halted)]; "equal jump")]
+    #[test_case("gtrr 0 0 5\nseti 99 0 5" => vec![(2, Some(0), Outcome::Halted)]; "greater jump")]
+    fn same_coefficient(program: &str) -> Vec<(usize, Option<Number>, Outcome)> {
+        let cpu: Cpu = format!("#ip 5\n{}", program).parse().unwrap();
+        Paths::new(&cpu, Bounds::default())
+            .map(|p| (p.cycles, p.domain.min(), p.outcome))
+            .collect()
+    }
+
+    #[test]
+    fn faults() {
+        let mut cpu: Cpu = "#ip 5\nseti 1 0 0".parse().unwrap();
+        cpu.program.push(Instruction::new(Op::Addr, 0, 9, 1));
+        let paths = Paths::new(&cpu, Bounds::default()).collect::<Vec<_>>();
+        assert_eq!(paths.len(), 1);
+        assert_eq!(
+            paths[0].outcome,
+            Outcome::Fault(CpuError::InvalidRegister { pc: 1, register: 9 })
+        );
+    }
+
+    #[test]
+    fn overflow_is_unsupported() {
+        let cpu: Cpu = "#ip 5
+muli 0 9223372036854775808 0
+muli 0 4611686018427387904 0
+addr 0 0 0
+addr 0 0 0"
+            .parse()
+            .unwrap();
+        let outcomes = Paths::new(&cpu, Bounds::default()).map(|p| p.outcome);
+        assert_eq!(outcomes.collect::<Vec<_>>(), vec![Outcome::Unsupported(3)]);
+    }
+
+    #[test]
+    fn indirect_jump() {
+        let cpu: Cpu = "#ip 5
+addr 5 0 5
+seti 7 0 1
+seti 8 0 1"
+            .parse()
+            .unwrap();
+        let paths = Paths::new(&cpu, Bounds::default()).collect::<Vec<_>>();
+        assert_eq!(paths.len(), 3);
+        assert_eq!(paths[0].domain.min(), Some(2));
+        assert_eq!(paths[0].cycles, 1);
+        assert_eq!(
+            paths[1..]
+                .iter()
+                .map(|p| (p.domain.min(), p.cycles))
+                .collect::<Vec<_>>(),
+            vec![(Some(1), 2), (Some(0), 3)]
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let cpu: Cpu = "#ip 5
+seti 0 0 1
+addi 0 1 0
+eqri 0 1000 1
+addr 1 5 5
+seti 0 0 5"
+            .parse()
+            .unwrap();
+        let bounds = Bounds {
+            max_branches: 3,
+            ..Bounds::default()
+        };
+        let outcomes = Paths::new(&cpu, bounds)
+            .map(|p| p.outcome)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outcomes,
+            vec![
+                Outcome::Halted,
+                Outcome::Halted,
+                Outcome::Halted,
+                Outcome::BranchLimit
+            ]
+        );
+        assert_eq!(fewest_cycles_to_halt(&cpu, bounds), Some((999, 4)));
+    }
+}
@@ -0,0 +1,127 @@
+use std::{
+    io::Write,
+    ops::{ControlFlow, Range},
+};
+
+use itertools::Itertools;
+
+use super::{Cpu, Limits, Number, StopReason};
+
+pub struct Tracer<W: Write> {
+    pub pcs: Range<Number>,
+    pub every: usize,
+    out: W,
+}
+
+impl<W: Write> Tracer<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            pcs: 0..Number::MAX,
+            every: 1,
+            out,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    // Stops the cpu at the first write error and returns that error.
+    pub fn run(&mut self, cpu: &mut Cpu, limits: &Limits) -> std::io::Result<StopReason> {
+        writeln!(self.out, "cycle\tpc\tinstruction\tbefore\tafter")?;
+        let mut error = None;
+        let reason = cpu.run_observed(limits, |pc, before, cpu| {
+            let cycle = cpu.cycles - 1;
+            if self.pcs.contains(&pc) && cycle.is_multiple_of(self.every.max(1)) {
+                if let Err(e) = writeln!(
+                    self.out,
+                    "{}\t{}\t{}\t{}\t{}",
+                    cycle,
+                    pc,
+                    cpu.program[pc],
+                    before.iter().join(","),
+                    cpu.registers.iter().join(",")
+                ) {
+                    error = Some(e);
+                    return ControlFlow::Break(());
+                }
+            }
+            ControlFlow::Continue(())
+        });
+        match error {
+            Some(e) => Err(e),
+            None => Ok(reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tracer {
+    use super::*;
+
+    fn example() -> Cpu {
+        include_str!("../day19_example.txt").parse().unwrap()
+    }
+
+    #[test]
+    fn every_instruction() {
+        let mut tracer = Tracer::new(vec![]);
+        let reason = tracer.run(&mut example(), &Limits::default()).unwrap();
+        assert_eq!(reason, StopReason::Halted);
+        assert_eq!(
+            String::from_utf8(tracer.into_inner()).unwrap(),
+            "cycle\tpc\tinstruction\tbefore\tafter
+0\t0\tseti 5 0 1\t0,0,0,0,0,0\t0,5,0,0,0,0
+1\t1\tseti 6 0 2\t1,5,0,0,0,0\t1,5,6,0,0,0
+2\t2\taddi 0 1 0\t2,5,6,0,0,0\t3,5,6,0,0,0
+3\t4\tsetr 1 0 0\t4,5,6,0,0,0\t5,5,6,0,0,0
+4\t6\tseti 9 0 5\t6,5,6,0,0,0\t6,5,6,0,0,9
+"
+        );
+    }
+
+    #[test]
+    fn filtered() {
+        let mut tracer = Tracer::new(vec![]);
+        tracer.pcs = 1..5;
+        tracer.every = 2;
+        tracer.run(&mut example(), &Limits::default()).unwrap();
+        let output = String::from_utf8(tracer.into_inner()).unwrap();
+        let pcs = output
+            .lines()
+            .skip(1)
+            .map(|l| l.split('\t').nth(1).unwrap())
+            .collect_vec();
+        assert_eq!(pcs, vec!["2"]);
+    }
+
+    // Accepts the header and `lines` more lines, then fails every write.
+    struct Full {
+        lines: usize,
+    }
+
+    impl Write for Full {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let newlines = buf.iter().filter(|&&b| b == b'\n').count();
+            if newlines > self.lines {
+                return Err(std::io::Error::other("disk full"));
+            }
+            self.lines -= newlines;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn stops_at_write_error() {
+        let mut cpu = example();
+        let mut tracer = Tracer::new(Full { lines: 3 });
+        let error = tracer.run(&mut cpu, &Limits::default()).unwrap_err();
+        assert_eq!(error.to_string(), "disk full");
+        assert_eq!(cpu.cycles, 3);
+        assert!(!cpu.halted());
+    }
+}